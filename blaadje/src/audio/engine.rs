@@ -1,18 +1,38 @@
-use super::modules::{Clock, Filter, Midi, Oscillator, Sample, Sequencer, Vca};
+use super::master::{Limiter, Master};
+use super::modules::{
+    ChokeGroups, Clock, Delay, Envelope, Filter, Midi, Mixer, Oscillator, Reverb, Sample,
+    Sequencer, Vca,
+};
 use super::System;
 use crate::core::{args, args_min};
-use crate::{Blad, Channel, Error, Literal, Screech};
-use screech::{Module, Patchbay, Processor, Signal};
+use crate::{Blad, ChannelReceiver, Error, Literal, Message, Screech};
+use screech::{Module, PatchPoint, Patchbay, Processor, Signal};
 use screech_macro::modularize;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Number of frames pushed to the system at once, control messages are handled
+/// between blocks. Modules still process one frame at a time.
+pub const BLOCK_SIZE: usize = 64;
+
+/// Number of interleaved output channels
+pub const CHANNELS: usize = 2;
+
+/// How long to wait for messages while there is no stream to render for
+const IDLE_WAIT: Duration = Duration::from_millis(50);
 
 #[modularize]
 enum Modules {
     Clock(Clock),
+    Delay(Delay),
+    Envelope(Envelope),
     Filter(Filter),
     Midi(Midi),
+    Mixer(Mixer),
     Oscillator(Oscillator),
+    Reverb(Reverb),
     Sample(Sample),
     Sequencer(Sequencer),
     Vca(Vca),
@@ -22,9 +42,13 @@ impl Modules {
     fn reset(&mut self) {
         match self {
             Modules::Clock(m) => m.reset(),
+            Modules::Delay(m) => m.reset(),
+            Modules::Envelope(m) => m.reset(),
             Modules::Filter(m) => m.reset(),
             Modules::Midi(m) => m.reset(),
+            Modules::Mixer(m) => m.reset(),
             Modules::Oscillator(m) => m.reset(),
+            Modules::Reverb(m) => m.reset(),
             Modules::Sample(m) => m.reset(),
             Modules::Sequencer(m) => m.reset(),
             Modules::Vca(m) => m.reset(),
@@ -34,21 +58,42 @@ impl Modules {
     fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
         match self {
             Modules::Clock(m) => m.set(list),
+            Modules::Delay(m) => m.set(list),
+            Modules::Envelope(m) => m.set(list),
             Modules::Filter(m) => m.set(list),
             Modules::Midi(m) => m.set(list),
+            Modules::Mixer(m) => m.set(list),
             Modules::Oscillator(m) => m.set(list),
+            Modules::Reverb(m) => m.set(list),
             Modules::Sample(m) => m.set(list),
             Modules::Sequencer(m) => m.set(list),
             Modules::Vca(m) => m.set(list),
         }
     }
 
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        match self {
+            Modules::Clock(m) => m.set_sample_rate(sample_rate),
+            Modules::Delay(m) => m.set_sample_rate(sample_rate),
+            Modules::Envelope(m) => m.set_sample_rate(sample_rate),
+            Modules::Filter(m) => m.set_sample_rate(sample_rate),
+            Modules::Oscillator(m) => m.set_sample_rate(sample_rate),
+            Modules::Reverb(m) => m.set_sample_rate(sample_rate),
+            Modules::Sample(m) => m.set_sample_rate(sample_rate),
+            Modules::Midi(_) | Modules::Mixer(_) | Modules::Sequencer(_) | Modules::Vca(_) => (),
+        }
+    }
+
     fn get(&self, list: &[Blad]) -> Result<Blad, Error> {
         match self {
             Modules::Clock(m) => m.get(list),
+            Modules::Delay(m) => m.get(list),
+            Modules::Envelope(m) => m.get(list),
             Modules::Filter(m) => m.get(list),
             Modules::Midi(m) => m.get(list),
+            Modules::Mixer(m) => m.get(list),
             Modules::Oscillator(m) => m.get(list),
+            Modules::Reverb(m) => m.get(list),
             Modules::Sample(m) => m.get(list),
             Modules::Sequencer(m) => m.get(list),
             Modules::Vca(m) => m.get(list),
@@ -58,47 +103,112 @@ impl Modules {
 
 pub struct Engine<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usize> {
     module_ids: HashMap<String, usize>,
+    /// Options every module was created with, they can't change afterwards
+    module_options: HashMap<String, Vec<Blad>>,
     patchbay: Patchbay<NUM_PATCHES>,
     processor: Processor<SAMPLE_RATE, NUM_MODULES, Modules>,
     outputs_left: Vec<Signal>,
     outputs_right: Vec<Signal>,
-    midi_buffer: Arc<Mutex<Vec<u32>>>,
     system: Box<dyn System>,
-    channels: Vec<Arc<Mutex<Channel>>>,
+    channels: Vec<ChannelReceiver>,
+    sample_rate: usize,
+    choke_groups: Arc<ChokeGroups>,
+    master: Master,
 }
 
 impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usize>
     Engine<SAMPLE_RATE, NUM_MODULES, NUM_PATCHES>
 {
-    pub fn new(system: Box<dyn System>, channels: Vec<Arc<Mutex<Channel>>>) -> Self {
+    pub fn new(mut system: Box<dyn System>, mut channels: Vec<ChannelReceiver>) -> Self {
+        // Incoming MIDI arrives as compact messages on the system's channel
+        if let Some(midi_channel) = system.take_midi_channel() {
+            channels.push(midi_channel);
+        }
+
         Self {
             module_ids: HashMap::new(),
+            module_options: HashMap::new(),
             patchbay: Patchbay::new(),
             processor: Processor::empty(),
             outputs_left: Vec::new(),
             outputs_right: Vec::new(),
-            midi_buffer: Arc::new(Mutex::new(Vec::new())),
             system,
             channels,
+            sample_rate: SAMPLE_RATE,
+            choke_groups: Arc::new(ChokeGroups::new()),
+            master: Master::new(SAMPLE_RATE as f32),
+        }
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Change the sample rate the modules render at, `SAMPLE_RATE` is only
+    /// used until the audio stream reports its actual rate.
+    pub fn set_sample_rate(&mut self, sample_rate: usize) {
+        self.sample_rate = sample_rate;
+        self.master.set_sample_rate(sample_rate as f32);
+
+        for id in self.module_ids.values() {
+            if let Some(module) = self.processor.get_module_mut(*id) {
+                module.set_sample_rate(sample_rate as f32);
+            }
+        }
+    }
+
+    /// Handle all pending control messages without blocking
+    pub fn process_channels(&mut self) {
+        // Move the receivers out so messages can mutate the engine
+        let mut channels = std::mem::take(&mut self.channels);
+
+        for channel in channels.iter_mut() {
+            while let Some(m) = channel.try_recv() {
+                self.handle(channel, m);
+            }
+        }
+
+        self.channels = channels;
+    }
+
+    /// Block until a control message arrives or `timeout` has passed, then
+    /// handle all pending messages
+    pub fn wait_for_messages(&mut self, timeout: Duration) {
+        let first = self.channels.first_mut().map(|c| c.recv_timeout(timeout));
+
+        match first {
+            Some(Some(m)) => {
+                let mut channels = std::mem::take(&mut self.channels);
+                self.handle(&mut channels[0], m);
+                self.channels = channels;
+            }
+            Some(None) => (),
+            None => thread::sleep(timeout),
         }
+
+        self.process_channels();
     }
 
-    pub fn process_channel(&mut self, channel: Arc<Mutex<Channel>>) {
-        let messages = {
-            let mut channel = channel.lock().unwrap();
-            let messages = channel.messages().to_owned();
-            channel.clear();
-            messages
+    fn handle(&mut self, channel: &mut ChannelReceiver, message: Message) {
+        let reply = match &message {
+            Message::Cast(body) | Message::Call(body, _) => self.process_message(body),
+            Message::Midi(midi) => return self.process_midi(*midi),
         };
 
-        for m in messages {
-            let reply = self.process_message(m);
-            let mut channel = channel.lock().unwrap();
-            channel.reply(reply);
+        channel.reply(message, reply);
+    }
+
+    /// Hand a MIDI message straight to the MIDI modules, they consume it
+    /// during the next sample
+    fn process_midi(&mut self, message: u32) {
+        for id in self.module_ids.values() {
+            if let Some(Modules::Midi(midi)) = self.processor.get_module_mut(*id) {
+                midi.queue(message);
+            }
         }
     }
 
-    fn process_message(&mut self, message: Blad) -> Result<Blad, Error> {
+    fn process_message(&mut self, message: &Blad) -> Result<Blad, Error> {
         let list = message.get_list()?;
         args_min(list, 1)?;
         let operator = &list[0].get_atom()?;
@@ -117,12 +227,49 @@ impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usiz
                         let sample_rate = &list[5].get_usize()?;
                         let bit_depth = &list[6].get_usize()?;
 
-                        self.system
+                        let sample_rate = self
+                            .system
                             .start_audio(host_id, device_id, *buffer_size, *sample_rate, *bit_depth)
-                            .unwrap();
+                            .map_err(|e| Error::SystemError(e.to_string()))?;
+
+                        self.set_sample_rate(sample_rate);
+
+                        Ok(Blad::Unit)
+                    }
+                    ":sample_rate" => Ok(Blad::Literal(Literal::I64(self.sample_rate as i64))),
+                    ":master_gain" => {
+                        args(&list, 3)?;
+                        self.master.set_gain(list[2].get_f32()?);
+
+                        Ok(Blad::Unit)
+                    }
+                    ":limiter" => {
+                        args(&list, 3)?;
+                        let limiter = match list[2].get_atom()? {
+                            ":none" => Limiter::None,
+                            ":soft_clip" => Limiter::SoftClip,
+                            ":brickwall" => Limiter::Brickwall,
+                            limiter => return Err(Error::InvalidProperty(limiter.into())),
+                        };
+                        self.master.set_limiter(limiter);
 
                         Ok(Blad::Unit)
                     }
+                    ":meters" => {
+                        let meters = self.master.meters();
+                        let level = |name: &str, (left, right): (f32, f32)| {
+                            Blad::List(vec![
+                                Blad::Atom(name.into()),
+                                Blad::Literal(Literal::F32(left)),
+                                Blad::Literal(Literal::F32(right)),
+                            ])
+                        };
+
+                        Ok(Blad::List(vec![
+                            level(":peak", meters.peak),
+                            level(":rms", meters.rms),
+                        ]))
+                    }
                     ":stop_audio" => {
                         self.system.stop_audio();
 
@@ -189,20 +336,25 @@ impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usiz
             }
             ":midi" => {
                 args(&list, 2)?;
-                let message = &list[1].get_usize()?;
-
-                let mut midi_buffer = self.midi_buffer.lock().unwrap();
-                midi_buffer.push(*message as u32);
+                let message = list[1].get_usize()? as u32;
+                self.process_midi(message);
 
                 Ok(Blad::Unit)
             }
             ":insert_module" => {
-                args(&list, 3)?;
+                args_min(&list, 3)?;
                 let atom = &list[1].get_atom()?;
                 let string_id = &list[2].get_string()?;
+                let options = &list[3..];
 
                 let id = match self.module_ids.get(*string_id) {
                     Some(id) => {
+                        // Options size the module, so it would have to be
+                        // created again and lose its connections
+                        if self.module_options.get(*string_id).map(Vec::as_slice) != Some(options) {
+                            return Err(Error::ModuleOptionsChanged(string_id.to_string()));
+                        }
+
                         let module = self.processor.get_module_mut(*id).unwrap();
 
                         module.reset();
@@ -210,12 +362,21 @@ impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usiz
                         *id
                     }
                     None => {
-                        let module = self
-                            .atom_to_module(atom)
-                            .ok_or(Error::UnknownModule(atom.to_string()))?;
-
-                        let id = self.processor.insert_module(module).unwrap();
+                        // Patch points can't be given back, so check for room
+                        // before the module takes any
+                        if self.module_ids.len() >= NUM_MODULES {
+                            return Err(Error::OutOfModules);
+                        }
+
+                        let module = self.atom_to_module(atom, options)?;
+
+                        let id = self
+                            .processor
+                            .insert_module(module)
+                            .ok_or(Error::OutOfModules)?;
                         self.module_ids.insert(string_id.to_string(), id);
+                        self.module_options
+                            .insert(string_id.to_string(), options.to_vec());
                         id
                     }
                 };
@@ -306,46 +467,107 @@ impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usiz
         }
     }
 
-    fn atom_to_module(&mut self, atom: &str) -> Option<Modules> {
+    /// Allocate a patch point for a module output
+    fn point(&mut self) -> Result<PatchPoint, Error> {
+        self.patchbay.point().ok_or(Error::OutOfPatchPoints)
+    }
+
+    /// Create a module, `options` are the arguments after the id
+    fn atom_to_module(&mut self, atom: &str, options: &[Blad]) -> Result<Modules, Error> {
+        let sample_rate = self.sample_rate as f32;
+
         match atom {
-            ":oscillator" => Some(Modules::Oscillator(Oscillator::new(
-                self.patchbay.point().unwrap(),
+            ":oscillator" => Ok(Modules::Oscillator(Oscillator::new(
+                self.point()?,
+                sample_rate,
+            ))),
+            ":envelope" => Ok(Modules::Envelope(Envelope::new(self.point()?, sample_rate))),
+            ":filter" => Ok(Modules::Filter(Filter::new(
+                self.point()?,
+                [
+                    self.point()?,
+                    self.point()?,
+                    self.point()?,
+                    self.point()?,
+                    self.point()?,
+                ],
+                sample_rate,
+            ))),
+            ":vca" => Ok(Modules::Vca(Vca::new(self.point()?))),
+            ":reverb" => Ok(Modules::Reverb(Reverb::new(
+                self.point()?,
+                self.point()?,
+                sample_rate,
+            ))),
+            ":sample" => Ok(Modules::Sample(Sample::new(
+                self.point()?,
+                self.point()?,
+                self.choke_groups.clone(),
+                sample_rate,
             ))),
-            ":filter" => Some(Modules::Filter(Filter::new(self.patchbay.point().unwrap()))),
-            ":vca" => Some(Modules::Vca(Vca::new(self.patchbay.point().unwrap()))),
-            ":sample" => Some(Modules::Sample(Sample::new(self.patchbay.point().unwrap()))),
-            ":clock" => Some(Modules::Clock(Clock::new(self.patchbay.point().unwrap()))),
+            ":clock" => Ok(Modules::Clock(Clock::new(self.point()?, sample_rate))),
+            ":delay" => {
+                // Longest delay in seconds
+                let max_time = match options.first() {
+                    Some(Blad::Literal(Literal::I64(i))) => *i as f32,
+                    Some(b) => b.get_f32()?,
+                    None => 4.0,
+                };
+
+                Ok(Modules::Delay(Delay::new(
+                    self.point()?,
+                    max_time,
+                    sample_rate,
+                )))
+            }
             ":midi" => {
                 let voices = 8;
                 let frequencies = (0..voices)
-                    .map(|_| self.patchbay.point().unwrap())
-                    .collect();
+                    .map(|_| self.point())
+                    .collect::<Result<_, _>>()?;
                 let gates = (0..voices)
-                    .map(|_| self.patchbay.point().unwrap())
-                    .collect();
-
-                Some(Modules::Midi(Midi::new(
-                    frequencies,
-                    gates,
-                    self.patchbay.point().unwrap(),
-                    self.midi_buffer.clone(),
+                    .map(|_| self.point())
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Modules::Midi(Midi::new(frequencies, gates, self.point()?)))
+            }
+            ":mixer" => {
+                let channels = match options.first() {
+                    Some(b) => b.get_usize()?,
+                    None => 8,
+                };
+                let sends = match options.get(1) {
+                    Some(b) => b.get_usize()?,
+                    None => 2,
+                };
+                let sends = (0..sends).map(|_| self.point()).collect::<Result<_, _>>()?;
+
+                Ok(Modules::Mixer(Mixer::new(
+                    self.point()?,
+                    self.point()?,
+                    sends,
+                    channels,
                 )))
             }
-            ":sequencer" => Some(Modules::Sequencer(Sequencer::new(
-                self.patchbay.point().unwrap(),
-                self.patchbay.point().unwrap(),
-                self.patchbay.point().unwrap(),
+            ":sequencer" => Ok(Modules::Sequencer(Sequencer::new(
+                self.point()?,
+                self.point()?,
+                self.point()?,
             ))),
-            _ => None,
+            _ => Err(Error::UnknownModule(atom.to_string())),
         }
     }
 
     fn module_to_atom(&self, id: usize) -> &str {
         match self.processor.get_module(id) {
             Some(Modules::Clock(_)) => ":clock",
+            Some(Modules::Delay(_)) => ":delay",
+            Some(Modules::Envelope(_)) => ":envelope",
             Some(Modules::Filter(_)) => ":filter",
             Some(Modules::Midi(_)) => ":midi",
+            Some(Modules::Mixer(_)) => ":mixer",
             Some(Modules::Oscillator(_)) => ":oscillator",
+            Some(Modules::Reverb(_)) => ":reverb",
             Some(Modules::Sample(_)) => ":sample",
             Some(Modules::Sequencer(_)) => ":sequencer",
             Some(Modules::Vca(_)) => ":vca",
@@ -354,26 +576,43 @@ impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usiz
     }
 
     pub fn process(&mut self) {
+        let mut block = [0.0; BLOCK_SIZE * CHANNELS];
+
         loop {
-            let channels = self.channels.clone();
-            for channel in channels {
-                self.process_channel(channel.clone());
+            // Nothing consumes audio without a stream, only handle messages
+            if !self.system.is_running() {
+                self.wait_for_messages(IDLE_WAIT);
+                continue;
             }
 
-            if !self.system.buffer_full() {
-                let (l, _) = self.next_samples();
-                self.system.push_sample(l);
+            // Control messages are only applied at block boundaries
+            self.process_channels();
+
+            if self.system.vacant_frames() < BLOCK_SIZE {
+                thread::yield_now();
+                continue;
             }
+
+            self.next_block(&mut block);
+            self.system.push_frames(&block);
         }
     }
 
+    /// Render interleaved stereo frames into `block` one at a time, through the
+    /// master stage
+    pub fn next_block(&mut self, block: &mut [f32]) {
+        for frame in block.chunks_exact_mut(CHANNELS) {
+            let samples = self.next_samples();
+            let (left, right) = self.master.process(samples);
+            frame[0] = left;
+            frame[1] = right;
+        }
+    }
+
+    /// Sum of the outputs for the next frame, before the master stage
     pub fn next_samples(&mut self) -> (f32, f32) {
         self.processor.process_modules(&mut self.patchbay);
 
-        {
-            self.midi_buffer.lock().unwrap().clear();
-        }
-
         let mut left = 0.0;
         let mut right = 0.0;
 
@@ -388,3 +627,287 @@ impl<const SAMPLE_RATE: usize, const NUM_MODULES: usize, const NUM_PATCHES: usiz
         (left, right)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{run_with_env, set_prelude, Buffer, Environment, NullSystem};
+    use std::sync::Mutex;
+
+    pub(crate) type TestEngine = Engine<44_100, 32, 128>;
+
+    pub(crate) fn new_engine() -> TestEngine {
+        TestEngine::new(Box::new(NullSystem::new()), vec![])
+    }
+
+    pub(crate) fn atom(s: &str) -> Blad {
+        Blad::Atom(s.to_string())
+    }
+
+    pub(crate) fn string(s: &str) -> Blad {
+        Blad::Literal(Literal::String(s.to_string()))
+    }
+
+    pub(crate) fn float(f: f32) -> Blad {
+        Blad::Literal(Literal::F32(f))
+    }
+
+    pub(crate) fn int(i: i64) -> Blad {
+        Blad::Literal(Literal::I64(i))
+    }
+
+    pub(crate) fn signal(f: f32) -> Blad {
+        Blad::Screech(Screech::Signal(Signal::Fixed(f)))
+    }
+
+    pub(crate) fn try_message(engine: &mut TestEngine, message: Vec<Blad>) -> Result<Blad, Error> {
+        engine.process_message(&Blad::List(message))
+    }
+
+    pub(crate) fn message(engine: &mut TestEngine, message: Vec<Blad>) -> Blad {
+        try_message(engine, message).unwrap()
+    }
+
+    pub(crate) fn insert(
+        engine: &mut TestEngine,
+        module: &str,
+        id: &str,
+        options: Vec<Blad>,
+    ) -> Blad {
+        let mut insert = vec![atom(":insert_module"), atom(module), string(id)];
+        insert.extend(options);
+
+        message(engine, insert)
+    }
+
+    pub(crate) fn set(engine: &mut TestEngine, module: &Blad, properties: Vec<(&str, Blad)>) {
+        let mut set = vec![atom(":set"), module.clone()];
+        set.extend(
+            properties
+                .into_iter()
+                .map(|(property, value)| Blad::List(vec![atom(property), value])),
+        );
+
+        message(engine, set);
+    }
+
+    pub(crate) fn get(engine: &mut TestEngine, module: &Blad, property: &str) -> Blad {
+        message(engine, vec![atom(":get"), module.clone(), atom(property)])
+    }
+
+    /// Send a module output to the left or right side of the engine output
+    pub(crate) fn connect(engine: &mut TestEngine, module: &Blad, property: &str, side: &str) {
+        let output = get(engine, module, property);
+        message(engine, vec![atom(side), output]);
+    }
+
+    /// Render frames and keep the left side
+    pub(crate) fn left(engine: &mut TestEngine, frames: usize) -> Vec<f32> {
+        (0..frames).map(|_| engine.next_samples().0).collect()
+    }
+
+    /// Insert a sample of four frames that is triggered right away
+    pub(crate) fn sample(engine: &mut TestEngine, id: &str, properties: Vec<(&str, Blad)>) -> Blad {
+        let sample = insert(engine, ":sample", id, vec![]);
+        let buffer = Buffer::new(vec![0.2, 0.4, 0.6, 0.8].into(), 44_100, 1);
+
+        let mut all = vec![
+            (":samples", Blad::Buffer(buffer)),
+            (":trigger", signal(1.0)),
+        ];
+        all.extend(properties);
+        set(engine, &sample, all);
+
+        sample
+    }
+
+    #[test]
+    fn silence_without_outputs() {
+        let mut engine = new_engine();
+
+        assert_eq!(engine.next_samples(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn block_matches_samples() {
+        let render = || {
+            let mut engine = new_engine();
+
+            let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+            connect(&mut engine, &osc, ":output", ":output_left");
+            connect(&mut engine, &osc, ":output", ":output_right");
+
+            engine
+        };
+
+        let mut block = [0.0; BLOCK_SIZE * CHANNELS];
+        render().next_block(&mut block);
+
+        let mut engine = render();
+        let mut master = Master::new(44_100.0);
+        let samples: Vec<f32> = (0..BLOCK_SIZE)
+            .flat_map(|_| {
+                let (l, r) = master.process(engine.next_samples());
+                [l, r]
+            })
+            .collect();
+
+        assert_eq!(block.to_vec(), samples);
+    }
+
+    #[test]
+    fn out_of_patch_points() {
+        let mut engine = new_engine();
+
+        // Every midi module takes 17 of the 128 patch points
+        let results: Vec<Result<Blad, Error>> = (0..8)
+            .map(|i| {
+                let id = string(&format!("midi{}", i));
+                try_message(&mut engine, vec![atom(":insert_module"), atom(":midi"), id])
+            })
+            .collect();
+
+        assert!(results[..7].iter().all(|r| r.is_ok()));
+        assert_eq!(results[7], Err(Error::OutOfPatchPoints));
+    }
+
+    #[test]
+    fn out_of_modules() {
+        let mut engine = new_engine();
+
+        // Every vca takes a single patch point, so the 32 modules run out first
+        let results: Vec<Result<Blad, Error>> = (0..33)
+            .map(|i| {
+                let id = string(&format!("vca{}", i));
+                try_message(&mut engine, vec![atom(":insert_module"), atom(":vca"), id])
+            })
+            .collect();
+
+        assert!(results[..32].iter().all(|r| r.is_ok()));
+        assert_eq!(results[32], Err(Error::OutOfModules));
+
+        // Existing modules can still be inserted again
+        assert_eq!(
+            insert(&mut engine, ":vca", "vca0", vec![]),
+            results[0].clone().unwrap()
+        );
+    }
+
+    #[test]
+    fn insert_module_reuses_id() {
+        let mut engine = new_engine();
+
+        let a = insert(&mut engine, ":vca", "vca", vec![]);
+        let b = insert(&mut engine, ":vca", "vca", vec![]);
+
+        assert_eq!(a, b);
+        assert_eq!(message(&mut engine, vec![atom(":module"), a]), atom(":vca"));
+    }
+
+    #[test]
+    fn runtime_sample_rate() {
+        let render = |sample_rate: usize| {
+            let mut engine = new_engine();
+
+            let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+            set(&mut engine, &osc, vec![(":waveshape", atom(":saw"))]);
+            connect(&mut engine, &osc, ":output", ":output_left");
+
+            engine.set_sample_rate(sample_rate);
+            engine.next_samples().0
+        };
+
+        let full_rate = render(44_100);
+        let half_rate = render(22_050);
+
+        assert!((half_rate - full_rate * 2.0).abs() < f32::EPSILON);
+    }
+
+    /// Evaluate a program while the engine answers its calls, like rendering
+    fn evaluate(engine: &mut TestEngine, code: &str, env: Arc<Mutex<Environment>>) {
+        let code = code.to_string();
+        let evaluation = thread::spawn(move || run_with_env(&code, env).unwrap());
+
+        while !evaluation.is_finished() {
+            engine.wait_for_messages(Duration::from_millis(1));
+        }
+
+        evaluation.join().unwrap();
+    }
+
+    #[test]
+    fn render_start_audio() {
+        let code = "(call (list :system :start_audio \"null\" \"null\" 1024 48000 16))";
+
+        let render = |system: NullSystem| {
+            let (env, channel) = Environment::new();
+            let env = Arc::new(Mutex::new(env));
+            set_prelude(env.clone()).unwrap();
+
+            let mut engine = TestEngine::new(Box::new(system), vec![channel]);
+            engine.set_sample_rate(44_100);
+            evaluate(&mut engine, code, env);
+
+            engine.sample_rate()
+        };
+
+        assert_eq!(render(NullSystem::new()), 48_000);
+        assert_eq!(render(NullSystem::new().with_sample_rate(44_100)), 44_100);
+    }
+
+    #[test]
+    fn system_devices() {
+        let mut engine = new_engine();
+
+        assert_eq!(
+            message(&mut engine, vec![atom(":system"), atom(":hosts")]),
+            Blad::List(vec![string("null")])
+        );
+
+        assert_eq!(
+            message(
+                &mut engine,
+                vec![atom(":system"), atom(":default_device"), string("null")]
+            ),
+            string("null")
+        );
+    }
+
+    #[test]
+    fn system_sample_rate() {
+        let mut engine = new_engine();
+        engine.set_sample_rate(48_000);
+
+        assert_eq!(
+            message(&mut engine, vec![atom(":system"), atom(":sample_rate")]),
+            int(48_000)
+        );
+    }
+
+    #[test]
+    fn master_limits_and_meters() {
+        let mut engine = new_engine();
+
+        let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+        connect(&mut engine, &osc, ":output", ":output_left");
+        message(
+            &mut engine,
+            vec![atom(":system"), atom(":master_gain"), float(20.0)],
+        );
+
+        let mut block = [0.0; BLOCK_SIZE * CHANNELS];
+        (0..1_000).for_each(|_| engine.next_block(&mut block));
+
+        let meters = message(&mut engine, vec![atom(":system"), atom(":meters")]);
+        let meters = meters.get_list().unwrap();
+        let peak = meters[0].get_list().unwrap();
+        let rms = meters[1].get_list().unwrap();
+
+        // The oscillator is at 0.1, so 2.0 before the limiter
+        assert!(block.iter().all(|s| s.abs() < 1.0));
+        assert!(peak[1].get_f32().unwrap() > 0.9);
+        assert!(peak[1].get_f32().unwrap() < 1.0);
+        assert!(rms[1].get_f32().unwrap() > 0.5);
+        assert_eq!(peak[2], float(0.0));
+    }
+}
@@ -0,0 +1,196 @@
+use std::f32::consts::PI;
+
+/// Highest level the limiter lets through
+const CEILING: f32 = 0.99;
+
+/// Seconds for the limiter to recover after a peak
+const RELEASE: f32 = 0.1;
+
+/// Seconds the RMS meter averages over
+const RMS_TIME: f32 = 0.3;
+
+/// Frequency in Hz below which the DC blocker cuts
+const DC_CUTOFF: f32 = 10.0;
+
+pub enum Limiter {
+    None,
+    /// Rounds off peaks with `tanh`, colors loud signals but never hard clips
+    SoftClip,
+    /// Turns the gain down as soon as a peak would go over the ceiling
+    Brickwall,
+}
+
+/// Peak and RMS levels of both channels, as linear amplitudes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Meters {
+    pub peak: (f32, f32),
+    pub rms: (f32, f32),
+}
+
+/// Final stage between the summed outputs and the device: removes DC offset,
+/// applies the master gain and limits the result, then meters it.
+pub struct Master {
+    gain: f32,
+    limiter: Limiter,
+    /// Previous input and output of the DC blocker per channel
+    dc: [(f32, f32); 2],
+    dc_coefficient: f32,
+    envelope: f32,
+    release_coefficient: f32,
+    /// Highest level since the meters were last read
+    peak: [f32; 2],
+    /// Running mean of the squared output
+    mean_square: [f32; 2],
+    rms_coefficient: f32,
+}
+
+impl Master {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut master = Self {
+            gain: 1.0,
+            limiter: Limiter::Brickwall,
+            dc: [(0.0, 0.0); 2],
+            dc_coefficient: 0.0,
+            envelope: 0.0,
+            release_coefficient: 0.0,
+            peak: [0.0; 2],
+            mean_square: [0.0; 2],
+            rms_coefficient: 0.0,
+        };
+
+        master.set_sample_rate(sample_rate);
+        master
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.dc_coefficient = 1.0 - 2.0 * PI * DC_CUTOFF / sample_rate;
+        self.release_coefficient = (-1.0 / (RELEASE * sample_rate)).exp();
+        self.rms_coefficient = (-1.0 / (RMS_TIME * sample_rate)).exp();
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    pub fn set_limiter(&mut self, limiter: Limiter) {
+        self.limiter = limiter;
+    }
+
+    /// Levels since the last call, the peaks start over afterwards
+    pub fn meters(&mut self) -> Meters {
+        let meters = Meters {
+            peak: (self.peak[0], self.peak[1]),
+            rms: (self.mean_square[0].sqrt(), self.mean_square[1].sqrt()),
+        };
+
+        self.peak = [0.0; 2];
+
+        meters
+    }
+
+    pub fn process(&mut self, (left, right): (f32, f32)) -> (f32, f32) {
+        let mut frame = [left, right];
+
+        for (sample, (x1, y1)) in frame.iter_mut().zip(self.dc.iter_mut()) {
+            let y = *sample - *x1 + self.dc_coefficient * *y1;
+            *x1 = *sample;
+            *y1 = y;
+            *sample = y * self.gain;
+        }
+
+        match self.limiter {
+            Limiter::None => (),
+            Limiter::SoftClip => {
+                for sample in frame.iter_mut() {
+                    *sample = sample.tanh();
+                }
+            }
+            Limiter::Brickwall => {
+                // Instant attack keeps every sample under the ceiling
+                let level = frame[0].abs().max(frame[1].abs());
+                self.envelope = if level > self.envelope {
+                    level
+                } else {
+                    level + (self.envelope - level) * self.release_coefficient
+                };
+
+                if self.envelope > CEILING {
+                    let reduction = CEILING / self.envelope;
+
+                    for sample in frame.iter_mut() {
+                        *sample *= reduction;
+                    }
+                }
+            }
+        }
+
+        for (i, sample) in frame.iter().enumerate() {
+            self.peak[i] = self.peak[i].max(sample.abs());
+            self.mean_square[i] =
+                sample * sample + (self.mean_square[i] - sample * sample) * self.rms_coefficient;
+        }
+
+        (frame[0], frame[1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_dc() {
+        let mut master = Master::new(44_100.0);
+
+        let frames: Vec<(f32, f32)> = (0..44_100).map(|_| master.process((0.5, -0.5))).collect();
+        let (left, right) = frames[frames.len() - 1];
+
+        assert!(left.abs() < 1e-3 && right.abs() < 1e-3);
+    }
+
+    #[test]
+    fn brickwall_limits() {
+        let mut master = Master::new(44_100.0);
+        master.set_gain(4.0);
+
+        // Alternating so the DC blocker passes it
+        let peak = (0..1_000)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .map(|x| master.process((x, x)).0.abs())
+            .fold(0.0, f32::max);
+
+        assert!(peak <= CEILING);
+        assert!(peak > 0.9);
+    }
+
+    #[test]
+    fn soft_clip_limits() {
+        let mut master = Master::new(44_100.0);
+        master.set_gain(4.0);
+        master.set_limiter(Limiter::SoftClip);
+
+        let (left, _) = master.process((1.0, 0.0));
+
+        assert!(left < 1.0 && left > 0.99);
+    }
+
+    #[test]
+    fn meters() {
+        let mut master = Master::new(44_100.0);
+        master.set_limiter(Limiter::None);
+
+        for i in 0..44_100 {
+            let x = if i % 2 == 0 { 0.5 } else { -0.5 };
+            master.process((x, 0.0));
+        }
+
+        let meters = master.meters();
+        assert!((meters.peak.0 - 0.5).abs() < 1e-2);
+        assert!((meters.rms.0 - 0.5).abs() < 1e-2);
+        assert_eq!(meters.peak.1, 0.0);
+
+        // Peaks start over after reading
+        master.process((0.1, 0.0));
+        assert!(master.meters().peak.0 < 0.2);
+    }
+}
@@ -1,6 +1,9 @@
 mod engine;
+mod master;
 mod modules;
+mod null_system;
 mod system;
 
-pub use engine::Engine;
+pub use engine::{Engine, BLOCK_SIZE, CHANNELS};
+pub use null_system::NullSystem;
 pub use system::System;
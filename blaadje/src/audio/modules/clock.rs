@@ -1,22 +1,29 @@
+use super::set_input;
 use crate::core::args_min;
-use crate::{Blad, Error, Literal, Screech};
+use crate::{Blad, Error, Screech};
 use screech::{Module, PatchPoint, Patchbay, Signal};
 
 pub struct Clock {
     output: PatchPoint,
     frequency: Signal,
     value: u32,
+    sample_rate: f32,
 }
 
 impl Clock {
-    pub fn new(output: PatchPoint) -> Self {
+    pub fn new(output: PatchPoint, sample_rate: f32) -> Self {
         Self {
             output,
             frequency: Signal::None,
             value: 0,
+            sample_rate,
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
     pub fn set_bpm(&mut self, bpm: Signal) -> &mut Self {
         self.frequency = bpm.scale(0.016666666);
         self
@@ -35,23 +42,14 @@ impl Clock {
             let value = &pair[1];
 
             match (property, value) {
-                (":frequency", Blad::Screech(Screech::Signal(signal))) => {
-                    self.frequency = *signal;
-                    Ok(Blad::Unit)
-                }
-                (":frequency", Blad::Literal(Literal::F32(frequency))) => {
-                    self.frequency = Signal::Fixed(*frequency);
-                    Ok(Blad::Unit)
-                }
-                (":bpm", Blad::Screech(Screech::Signal(signal))) => {
-                    self.set_bpm(*signal);
-                    Ok(Blad::Unit)
-                }
-                (":bpm", Blad::Literal(Literal::F32(bpm))) => {
-                    self.set_bpm(Signal::Fixed(*bpm));
+                (":bpm", _) => {
+                    let mut bpm = Signal::None;
+                    set_input(Some(&mut bpm), property, value)?;
+
+                    self.set_bpm(bpm);
                     Ok(Blad::Unit)
                 }
-                (a, b) => Err(Error::IncorrectPropertyPair(a.to_string(), b.clone())),
+                _ => set_input(self.input(property), property, value),
             }?;
         }
 
@@ -67,12 +65,19 @@ impl Clock {
             _ => Err(Error::InvalidProperty(property.into())),
         }
     }
+
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":frequency" => Some(&mut self.frequency),
+            _ => None,
+        }
+    }
 }
 
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Clock {
     fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
         let step =
-            ((patchbay.get(self.frequency) * (u32::MAX as f32)) / SAMPLE_RATE as f32 / 2.0) as u32;
+            ((patchbay.get(self.frequency) * (u32::MAX as f32)) / self.sample_rate / 2.0) as u32;
 
         self.value = self.value.wrapping_add(step);
 
@@ -0,0 +1,250 @@
+use super::set_input;
+use crate::core::args_min;
+use crate::{Blad, Error, Screech};
+use screech::{Module, PatchPoint, Patchbay, Signal};
+
+/// Echo with feedback. The delay time is in seconds, or in beats once `:bpm`
+/// is set so the echoes stay in time with a clock.
+pub struct Delay {
+    input: Signal,
+    time: Signal,
+    bpm: Signal,
+    feedback: Signal,
+    mix: Signal,
+    output: PatchPoint,
+    /// Longest delay in seconds, the buffer never grows while playing
+    max_time: f32,
+    buffer: Vec<f32>,
+    /// Position the next frame is written to
+    position: usize,
+    sample_rate: f32,
+}
+
+impl Delay {
+    pub fn new(output: PatchPoint, max_time: f32, sample_rate: f32) -> Self {
+        Self {
+            input: Signal::None,
+            time: Signal::Fixed(0.25),
+            bpm: Signal::None,
+            feedback: Signal::Fixed(0.3),
+            mix: Signal::Fixed(0.5),
+            output,
+            max_time,
+            buffer: vec![0.0; buffer_length(max_time, sample_rate)],
+            position: 0,
+            sample_rate,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.buffer = vec![0.0; buffer_length(self.max_time, sample_rate)];
+        self.position = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.input = Signal::None;
+        self.time = Signal::Fixed(0.25);
+        self.bpm = Signal::None;
+        self.feedback = Signal::Fixed(0.3);
+        self.mix = Signal::Fixed(0.5);
+    }
+
+    pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+
+        for b in list.iter() {
+            let pair = b.get_list()?;
+            let property = pair[0].get_atom()?;
+            let value = &pair[1];
+
+            match (property, value) {
+                (":bpm", Blad::Unit) => {
+                    self.bpm = Signal::None;
+                    Ok(Blad::Unit)
+                }
+                _ => set_input(self.input(property), property, value),
+            }?;
+        }
+
+        Ok(Blad::Unit)
+    }
+
+    pub fn get(&self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+        let property = list[0].get_atom()?;
+
+        match property {
+            ":output" => Ok(Blad::Screech(Screech::Signal(self.output.signal()))),
+            _ => Err(Error::InvalidProperty(property.into())),
+        }
+    }
+
+    /// The input a property connects to
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":input" => Some(&mut self.input),
+            ":time" => Some(&mut self.time),
+            ":bpm" => Some(&mut self.bpm),
+            ":feedback" => Some(&mut self.feedback),
+            ":mix" => Some(&mut self.mix),
+            _ => None,
+        }
+    }
+
+    /// Read the frame written `frames` ago, in between frames when fractional
+    fn read(&self, frames: f32) -> f32 {
+        let length = self.buffer.len();
+        let whole = frames.floor();
+        let fraction = frames - whole;
+
+        let at = |offset: usize| self.buffer[(self.position + length - offset) % length];
+        let newer = at(whole as usize);
+        let older = at(whole as usize + 1);
+
+        newer + (older - newer) * fraction
+    }
+}
+
+impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Delay {
+    fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
+        patchbay.check(self.input)
+            && patchbay.check(self.time)
+            && patchbay.check(self.bpm)
+            && patchbay.check(self.feedback)
+            && patchbay.check(self.mix)
+    }
+
+    fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
+        let input = patchbay.get(self.input);
+
+        let seconds = match self.bpm {
+            Signal::None => patchbay.get(self.time),
+            bpm => patchbay.get(self.time) * 60.0 / patchbay.get(bpm).max(1.0),
+        };
+
+        // At least one frame, and one short of the buffer to interpolate with
+        let frames = (seconds * self.sample_rate).clamp(1.0, (self.buffer.len() - 2) as f32);
+        let delayed = self.read(frames);
+
+        // Keep feedback below 1 so the echoes always die out
+        let feedback = patchbay.get(self.feedback).clamp(-0.99, 0.99);
+        let mix = patchbay.get(self.mix).clamp(0.0, 1.0);
+
+        self.buffer[self.position] = input + delayed * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        patchbay.set(&mut self.output, input * (1.0 - mix) + delayed * mix);
+    }
+}
+
+/// Room for at least one frame of delay, plus two to interpolate with
+fn buffer_length(max_time: f32, sample_rate: f32) -> usize {
+    (max_time * sample_rate).ceil().max(1.0) as usize + 2
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+    use crate::Error;
+
+    #[test]
+    fn fractional_time() {
+        let mut engine = new_engine();
+
+        let delay = insert(&mut engine, ":delay", "delay", vec![]);
+        set(
+            &mut engine,
+            &delay,
+            vec![
+                (":input", signal(1.0)),
+                (":time", float(10.5 / 44_100.0)),
+                (":feedback", float(0.0)),
+                (":mix", float(1.0)),
+            ],
+        );
+        connect(&mut engine, &delay, ":output", ":output_left");
+
+        let values = left(&mut engine, 12);
+
+        assert!(values[..10].iter().all(|v| *v == 0.0));
+        assert!((values[10] - 0.5).abs() < 1e-4);
+        assert!((values[11] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn feedback_in_beats() {
+        let mut engine = new_engine();
+
+        let delay = insert(&mut engine, ":delay", "delay", vec![]);
+        // A 16th note at 6000 bpm is 110.25 frames
+        set(
+            &mut engine,
+            &delay,
+            vec![
+                (":input", signal(1.0)),
+                (":bpm", int(6000)),
+                (":time", float(0.25)),
+                (":feedback", float(0.5)),
+                (":mix", float(1.0)),
+            ],
+        );
+        connect(&mut engine, &delay, ":output", ":output_left");
+
+        let values = left(&mut engine, 2_000);
+
+        assert_eq!(values[109], 0.0);
+        assert!((values[110] - 0.75).abs() < 1e-4);
+        assert!((values[111] - 1.0).abs() < 1e-4);
+        assert!((values[222] - 1.5).abs() < 1e-4);
+        // Settles at input / (1 - feedback)
+        assert!((values[1_999] - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn max_time() {
+        let mut engine = new_engine();
+
+        // Room for 45 frames, a whole second is cut short
+        let delay = insert(&mut engine, ":delay", "delay", vec![float(0.001)]);
+        set(
+            &mut engine,
+            &delay,
+            vec![
+                (":input", signal(1.0)),
+                (":time", int(1)),
+                (":feedback", float(0.0)),
+                (":mix", float(1.0)),
+            ],
+        );
+        connect(&mut engine, &delay, ":output", ":output_left");
+
+        let values = left(&mut engine, 46);
+
+        assert_eq!(values[44], 0.0);
+        assert_eq!(values[45], 1.0);
+    }
+
+    #[test]
+    fn options_changed() {
+        let mut engine = new_engine();
+
+        let delay = insert(&mut engine, ":delay", "delay", vec![float(0.5)]);
+        assert_eq!(
+            insert(&mut engine, ":delay", "delay", vec![float(0.5)]),
+            delay
+        );
+
+        // A different size can't be applied to the existing buffer
+        let message = vec![
+            atom(":insert_module"),
+            atom(":delay"),
+            string("delay"),
+            float(2.0),
+        ];
+        assert_eq!(
+            try_message(&mut engine, message),
+            Err(Error::ModuleOptionsChanged("delay".into()))
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use super::set_input;
+use crate::core::args_min;
+use crate::{Blad, Error, Screech};
+use screech::{Module, PatchPoint, Patchbay, Signal};
+
+/// Steepness of exponential curves, higher is steeper
+const CURVATURE: f32 = 5.0;
+
+enum Curve {
+    Linear,
+    Exponential,
+}
+
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// ADSR envelope, started by a rising `:gate` or `:trigger` and released when
+/// the gate falls. Without a held gate it releases right after the decay.
+pub struct Envelope {
+    gate: Signal,
+    trigger: Signal,
+    attack: Signal,
+    decay: Signal,
+    sustain: Signal,
+    release: Signal,
+    curve: Curve,
+    output: PatchPoint,
+    stage: Stage,
+    /// Progress through the current stage, from 0 to 1
+    progress: f32,
+    /// Value at the start of the current stage
+    from: f32,
+    value: f32,
+    last_gate: f32,
+    last_trigger: f32,
+    sample_rate: f32,
+}
+
+impl Envelope {
+    pub fn new(output: PatchPoint, sample_rate: f32) -> Self {
+        Self {
+            gate: Signal::None,
+            trigger: Signal::None,
+            attack: Signal::Fixed(0.01),
+            decay: Signal::Fixed(0.1),
+            sustain: Signal::Fixed(0.7),
+            release: Signal::Fixed(0.3),
+            curve: Curve::Linear,
+            output,
+            stage: Stage::Idle,
+            progress: 0.0,
+            from: 0.0,
+            value: 0.0,
+            last_gate: 0.0,
+            last_trigger: 0.0,
+            sample_rate,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.gate = Signal::None;
+        self.trigger = Signal::None;
+        self.attack = Signal::Fixed(0.01);
+        self.decay = Signal::Fixed(0.1);
+        self.sustain = Signal::Fixed(0.7);
+        self.release = Signal::Fixed(0.3);
+        self.curve = Curve::Linear;
+    }
+
+    pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+
+        for b in list.iter() {
+            let pair = b.get_list()?;
+            let property = pair[0].get_atom()?;
+            let value = &pair[1];
+
+            match (property, value) {
+                (":curve", Blad::Atom(string)) => {
+                    match string.as_ref() {
+                        ":exponential" => self.curve = Curve::Exponential,
+                        _ => self.curve = Curve::Linear,
+                    };
+                    Ok(Blad::Unit)
+                }
+                _ => set_input(self.input(property), property, value),
+            }?;
+        }
+
+        Ok(Blad::Unit)
+    }
+
+    pub fn get(&self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+        let property = list[0].get_atom()?;
+
+        match property {
+            ":output" => Ok(Blad::Screech(Screech::Signal(self.output.signal()))),
+            _ => Err(Error::InvalidProperty(property.into())),
+        }
+    }
+
+    /// The input a property connects to
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":gate" => Some(&mut self.gate),
+            ":trigger" => Some(&mut self.trigger),
+            ":attack" => Some(&mut self.attack),
+            ":decay" => Some(&mut self.decay),
+            ":sustain" => Some(&mut self.sustain),
+            ":release" => Some(&mut self.release),
+            _ => None,
+        }
+    }
+
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.progress = 0.0;
+        self.from = self.value;
+    }
+
+    /// Move through the current stage, returns whether it has finished
+    fn advance(&mut self, seconds: f32, to: f32) -> bool {
+        self.progress = if seconds > 0.0 {
+            (self.progress + 1.0 / (seconds * self.sample_rate)).min(1.0)
+        } else {
+            1.0
+        };
+
+        let shaped = match self.curve {
+            Curve::Linear => self.progress,
+            Curve::Exponential => {
+                (1.0 - (-CURVATURE * self.progress).exp()) / (1.0 - (-CURVATURE).exp())
+            }
+        };
+
+        self.value = self.from + (to - self.from) * shaped;
+
+        self.progress >= 1.0
+    }
+}
+
+impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Envelope {
+    fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
+        patchbay.check(self.gate)
+            && patchbay.check(self.trigger)
+            && patchbay.check(self.attack)
+            && patchbay.check(self.decay)
+            && patchbay.check(self.sustain)
+            && patchbay.check(self.release)
+    }
+
+    fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
+        let gate = patchbay.get(self.gate);
+        let trigger = patchbay.get(self.trigger);
+        let held = gate > 0.0;
+
+        if (held && self.last_gate <= 0.0) || (trigger > 0.0 && self.last_trigger <= 0.0) {
+            self.enter(Stage::Attack);
+        } else if !held && self.last_gate > 0.0 {
+            self.enter(Stage::Release);
+        }
+
+        self.last_gate = gate;
+        self.last_trigger = trigger;
+
+        let sustain = patchbay.get(self.sustain).clamp(0.0, 1.0);
+
+        match self.stage {
+            Stage::Idle => self.value = 0.0,
+            Stage::Attack => {
+                if self.advance(patchbay.get(self.attack), 1.0) {
+                    self.enter(Stage::Decay);
+                }
+            }
+            Stage::Decay => {
+                if self.advance(patchbay.get(self.decay), sustain) {
+                    self.enter(if held { Stage::Sustain } else { Stage::Release });
+                }
+            }
+            Stage::Sustain => {
+                self.value = sustain;
+
+                if !held {
+                    self.enter(Stage::Release);
+                }
+            }
+            Stage::Release => {
+                if self.advance(patchbay.get(self.release), 0.0) {
+                    self.enter(Stage::Idle);
+                }
+            }
+        }
+
+        patchbay.set(&mut self.output, self.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+
+    #[test]
+    fn stages() {
+        let mut engine = new_engine();
+
+        let envelope = insert(&mut engine, ":envelope", "env", vec![]);
+        let seconds = |frames: f32| float(frames / 44_100.0);
+        set(
+            &mut engine,
+            &envelope,
+            vec![
+                (":gate", signal(1.0)),
+                (":attack", seconds(4.0)),
+                (":decay", seconds(2.0)),
+                (":sustain", float(0.5)),
+            ],
+        );
+        connect(&mut engine, &envelope, ":output", ":output_left");
+
+        let values = left(&mut engine, 8);
+
+        assert!(values[..4].windows(2).all(|w| w[0] < w[1]));
+        assert!((values[3] - 1.0).abs() < 1e-4);
+        assert!((values[5] - 0.5).abs() < 1e-4);
+        assert_eq!(values[7], 0.5);
+    }
+}
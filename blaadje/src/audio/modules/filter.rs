@@ -1,38 +1,67 @@
+use super::set_input;
 use crate::core::args_min;
-use crate::{Blad, Error, Literal, Screech};
+use crate::{Blad, Error, Screech};
 use screech::{Module, PatchPoint, Patchbay, Signal};
+use std::f32::consts::PI;
 
-const PI: f32 = 3.141;
+/// Lowest cutoff frequency in Hz
+const MIN_FREQUENCY: f32 = 10.0;
 
+/// Highest cutoff frequency as a fraction of the sample rate, just below Nyquist
+const MAX_FREQUENCY: f32 = 0.49;
+
+/// Lowest resonance, lower values would make the filter blow up
+const MIN_RESONANCE: f32 = 0.05;
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+    Allpass,
+}
+
+/// State variable filter using the topology-preserving transform, which stays
+/// stable when the cutoff is modulated at audio rate. All modes are available
+/// at the same time on their own outputs, `:output` follows `:mode`.
 pub struct Filter {
     input: Signal,
     frequency: Signal,
     resonance: Signal,
+    mode: Mode,
     output: PatchPoint,
-    x1: f32,
-    x2: f32,
-    y1: f32,
-    y2: f32,
+    /// Outputs for every mode, in the order of `Mode`
+    mode_outputs: [PatchPoint; 5],
+    ic1eq: f32,
+    ic2eq: f32,
+    sample_rate: f32,
 }
 
 impl Filter {
-    pub fn new(output: PatchPoint) -> Self {
+    pub fn new(output: PatchPoint, mode_outputs: [PatchPoint; 5], sample_rate: f32) -> Self {
         Self {
             input: Signal::None,
-            frequency: Signal::None,
+            frequency: Signal::Fixed(1000.0),
             resonance: Signal::Fixed(1.8),
+            mode: Mode::Lowpass,
             output,
-            x1: 0.0,
-            x2: 0.0,
-            y1: 0.0,
-            y2: 0.0,
+            mode_outputs,
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+            sample_rate,
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
     pub fn reset(&mut self) {
         self.input = Signal::None;
-        self.frequency = Signal::None;
+        self.frequency = Signal::Fixed(1000.0);
         self.resonance = Signal::Fixed(1.8);
+        self.mode = Mode::Lowpass;
     }
 
     pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
@@ -44,27 +73,17 @@ impl Filter {
             let value = &pair[1];
 
             match (property, value) {
-                (":input", Blad::Screech(Screech::Signal(signal))) => {
-                    self.input = *signal;
-                    Ok(Blad::Unit)
-                }
-                (":frequency", Blad::Screech(Screech::Signal(signal))) => {
-                    self.frequency = *signal;
+                (":mode", Blad::Atom(string)) => {
+                    match string.as_ref() {
+                        ":highpass" => self.mode = Mode::Highpass,
+                        ":bandpass" => self.mode = Mode::Bandpass,
+                        ":notch" => self.mode = Mode::Notch,
+                        ":allpass" => self.mode = Mode::Allpass,
+                        _ => self.mode = Mode::Lowpass,
+                    };
                     Ok(Blad::Unit)
                 }
-                (":frequency", Blad::Literal(Literal::F32(frequency))) => {
-                    self.frequency = Signal::Fixed(*frequency);
-                    Ok(Blad::Unit)
-                }
-                (":resonance", Blad::Screech(Screech::Signal(signal))) => {
-                    self.resonance = *signal;
-                    Ok(Blad::Unit)
-                }
-                (":resonance", Blad::Literal(Literal::F32(q))) => {
-                    self.resonance = Signal::Fixed(*q);
-                    Ok(Blad::Unit)
-                }
-                (a, b) => Err(Error::IncorrectPropertyPair(a.to_string(), b.clone())),
+                _ => set_input(self.input(property), property, value),
             }?;
         }
 
@@ -75,9 +94,25 @@ impl Filter {
         args_min(list, 1)?;
         let property = list[0].get_atom()?;
 
+        let output = match property {
+            ":output" => &self.output,
+            ":lowpass_output" => &self.mode_outputs[Mode::Lowpass as usize],
+            ":highpass_output" => &self.mode_outputs[Mode::Highpass as usize],
+            ":bandpass_output" => &self.mode_outputs[Mode::Bandpass as usize],
+            ":notch_output" => &self.mode_outputs[Mode::Notch as usize],
+            ":allpass_output" => &self.mode_outputs[Mode::Allpass as usize],
+            _ => return Err(Error::InvalidProperty(property.into())),
+        };
+
+        Ok(Blad::Screech(Screech::Signal(output.signal())))
+    }
+
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
         match property {
-            ":output" => Ok(Blad::Screech(Screech::Signal(self.output.signal()))),
-            _ => Err(Error::InvalidProperty(property.into())),
+            ":input" => Some(&mut self.input),
+            ":frequency" => Some(&mut self.frequency),
+            ":resonance" => Some(&mut self.resonance),
+            _ => None,
         }
     }
 }
@@ -85,30 +120,109 @@ impl Filter {
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Filter {
     fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
         patchbay.check(self.input)
+            && patchbay.check(self.frequency)
+            && patchbay.check(self.resonance)
     }
 
     fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
         let input = patchbay.get(self.input);
 
-        let omega = 2.0 * PI * patchbay.get(self.frequency) / SAMPLE_RATE as f32;
-        let alpha = f32::sin(omega) / (2.0 * patchbay.get(self.resonance));
-        let cos_omega = f32::cos(omega);
+        // At very low sample rates the highest cutoff drops below the lowest
+        let max_frequency = (MAX_FREQUENCY * self.sample_rate).max(MIN_FREQUENCY);
+        let frequency = patchbay
+            .get(self.frequency)
+            .clamp(MIN_FREQUENCY, max_frequency);
+        let resonance = patchbay.get(self.resonance).max(MIN_RESONANCE);
 
-        // Biquad formula
-        let norm = 1.0 / (1.0 + alpha);
-        let a0 = (1.0 - cos_omega) / 2.0 * norm;
-        let a1 = (1.0 - cos_omega) * norm;
-        let a2 = a0;
-        let b1 = -2.0 * cos_omega * norm;
-        let b2 = (1.0 - alpha) * norm;
+        let g = f32::tan(PI * frequency / self.sample_rate);
+        let k = 1.0 / resonance;
 
-        let output = a0 * input + a1 * self.x1 + a2 * self.x2 - b1 * self.y1 - b2 * self.y2;
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
 
-        patchbay.set(&mut self.output, output);
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        let lowpass = v2;
+        let bandpass = v1;
+        let highpass = input - k * v1 - v2;
+        let notch = lowpass + highpass;
+        let allpass = notch - k * bandpass;
+
+        let outputs = [lowpass, highpass, bandpass, notch, allpass];
+
+        for (point, value) in self.mode_outputs.iter_mut().zip(outputs) {
+            patchbay.set(point, value);
+        }
+
+        patchbay.set(&mut self.output, outputs[self.mode as usize]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+
+    #[test]
+    fn modes() {
+        let mut engine = new_engine();
+
+        let filter = insert(&mut engine, ":filter", "filter", vec![]);
+        set(
+            &mut engine,
+            &filter,
+            vec![(":input", signal(1.0)), (":mode", atom(":highpass"))],
+        );
+        connect(&mut engine, &filter, ":output", ":output_left");
+        connect(&mut engine, &filter, ":lowpass_output", ":output_right");
+
+        // A constant input only passes the lowpass once it has settled
+        let (highpass, lowpass) = (0..2_000).map(|_| engine.next_samples()).last().unwrap();
+
+        assert!(highpass.abs() < 1e-3);
+        assert!((lowpass - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cutoff_clamped() {
+        let mut engine = new_engine();
+
+        let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+        let filter = insert(&mut engine, ":filter", "filter", vec![]);
+        let input = get(&mut engine, &osc, ":output");
+        set(
+            &mut engine,
+            &filter,
+            vec![(":input", input), (":frequency", float(40_000.0))],
+        );
+        connect(&mut engine, &filter, ":output", ":output_left");
+
+        assert!(left(&mut engine, 10_000).iter().all(|v| v.abs() < 1.0));
+    }
 
-        self.x2 = self.x1;
-        self.x1 = input;
-        self.y2 = self.y1;
-        self.y1 = output;
+    #[test]
+    fn low_sample_rate() {
+        let mut engine = new_engine();
+        engine.set_sample_rate(16);
+
+        let filter = insert(&mut engine, ":filter", "filter", vec![]);
+        set(
+            &mut engine,
+            &filter,
+            vec![
+                (":input", signal(1.0)),
+                (":frequency", int(20)),
+                (":resonance", int(1)),
+            ],
+        );
+        connect(&mut engine, &filter, ":output", ":output_left");
+
+        // The cutoff is held at its lowest instead of panicking
+        assert_eq!(left(&mut engine, 10).len(), 10);
     }
 }
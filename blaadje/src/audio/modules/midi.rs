@@ -3,7 +3,9 @@ use crate::core::notes::midi_to_pitch;
 use crate::{Blad, Error, Literal, Screech};
 use screech::{Module, PatchPoint, Patchbay};
 use std::convert::From;
-use std::sync::{Arc, Mutex};
+
+/// Maximum number of messages handled per sample, the rest is dropped
+const MAX_MESSAGES: usize = 64;
 
 #[derive(Debug)]
 pub enum MidiMessage {
@@ -48,7 +50,7 @@ impl Voice {
 pub struct Midi {
     voices: Vec<Voice>,
     clock: PatchPoint,
-    buffer: Arc<Mutex<Vec<u32>>>,
+    messages: Vec<u32>,
     channel: u8,
 }
 
@@ -57,7 +59,6 @@ impl Midi {
         frequency_outs: Vec<PatchPoint>,
         gate_outs: Vec<PatchPoint>,
         clock: PatchPoint,
-        buffer: Arc<Mutex<Vec<u32>>>,
     ) -> Self {
         let voices = frequency_outs
             .into_iter()
@@ -68,11 +69,18 @@ impl Midi {
         Self {
             voices,
             clock,
-            buffer,
+            messages: Vec::with_capacity(MAX_MESSAGES),
             channel: 0,
         }
     }
 
+    /// Queue a raw MIDI message for the next sample without allocating
+    pub fn queue(&mut self, message: u32) {
+        if self.messages.len() < MAX_MESSAGES {
+            self.messages.push(message);
+        }
+    }
+
     pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
         args_min(list, 1)?;
 
@@ -82,7 +90,7 @@ impl Midi {
             let value = &pair[1];
 
             match (property, value) {
-                (":channel", Blad::Literal(Literal::Usize(channel))) => {
+                (":channel", Blad::Literal(Literal::I64(channel))) => {
                     self.channel = *channel as u8;
                     Ok(Blad::Unit)
                 }
@@ -122,8 +130,6 @@ impl Midi {
 
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Midi {
     fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
-        let messages = self.buffer.lock().unwrap();
-
         patchbay.set(&mut self.clock, 0.0);
 
         for v in self.voices.iter_mut() {
@@ -133,7 +139,7 @@ impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Midi {
             patchbay.set(&mut v.gate, patchbay.get(g));
         }
 
-        for message in messages.iter() {
+        for message in self.messages.iter() {
             match MidiMessage::from(*message) {
                 MidiMessage::TimingClock => patchbay.set(&mut self.clock, 1.0),
                 MidiMessage::NoteOn(channel, note, _velocity) if channel == self.channel => {
@@ -159,5 +165,34 @@ impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Midi {
                 _ => (),
             }
         }
+
+        self.messages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+    use crate::NullSystem;
+
+    #[test]
+    fn scripted() {
+        // Note on, channel 0, middle C, velocity 100
+        let note_on = 0x90 | (60 << 8) | (100 << 16);
+        let system = NullSystem::new().with_midi(&[note_on]);
+        let mut engine = TestEngine::new(Box::new(system), vec![]);
+
+        let midi = insert(&mut engine, ":midi", "midi", vec![]);
+        let voices = get(&mut engine, &midi, ":voices");
+        let voice = voices.get_list().unwrap()[0].get_list().unwrap().to_vec();
+
+        message(&mut engine, vec![atom(":output_left"), voice[0].clone()]);
+        message(&mut engine, vec![atom(":output_right"), voice[1].clone()]);
+
+        engine.process_channels();
+        let (frequency, gate) = engine.next_samples();
+
+        assert!(frequency > 0.0);
+        assert_eq!(gate, 1.0);
     }
 }
@@ -0,0 +1,286 @@
+use super::set_input;
+use crate::core::args_min;
+use crate::{Blad, Error, Screech};
+use screech::{Module, PatchPoint, Patchbay, Signal};
+use std::f32::consts::FRAC_PI_4;
+
+struct Channel {
+    input: Signal,
+    gain: Signal,
+    /// From -1.0 for left to 1.0 for right
+    pan: Signal,
+    /// Level sent to every auxiliary bus, after the gain
+    sends: Vec<Signal>,
+    mute: bool,
+    solo: bool,
+}
+
+impl Channel {
+    fn new(sends: usize) -> Self {
+        Self {
+            input: Signal::None,
+            gain: Signal::Fixed(1.0),
+            pan: Signal::Fixed(0.0),
+            sends: vec![Signal::Fixed(0.0); sends],
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+/// Mixes its channels down to stereo with a constant-power pan, and to mono
+/// auxiliary buses for effects like reverb. Per channel properties are set as
+/// a list with a value for every channel.
+pub struct Mixer {
+    channels: Vec<Channel>,
+    output_left: PatchPoint,
+    output_right: PatchPoint,
+    sends: Vec<PatchPoint>,
+}
+
+impl Mixer {
+    pub fn new(
+        output_left: PatchPoint,
+        output_right: PatchPoint,
+        sends: Vec<PatchPoint>,
+        channels: usize,
+    ) -> Self {
+        Self {
+            channels: (0..channels).map(|_| Channel::new(sends.len())).collect(),
+            output_left,
+            output_right,
+            sends,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        let sends = self.sends.len();
+
+        for channel in self.channels.iter_mut() {
+            *channel = Channel::new(sends);
+        }
+    }
+
+    pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+
+        for b in list.iter() {
+            let pair = b.get_list()?;
+            let property = pair[0].get_atom()?;
+            let value = &pair[1];
+
+            let values = match value {
+                Blad::List(vs) if vs.len() <= self.channels.len() => vs,
+                _ => return Err(Error::IncorrectPropertyPair(property.into(), value.clone())),
+            };
+
+            for (channel, v) in self.channels.iter_mut().zip(values) {
+                match property {
+                    ":inputs" => connect(&mut channel.input, property, v)?,
+                    ":gains" => connect(&mut channel.gain, property, v)?,
+                    ":pans" => connect(&mut channel.pan, property, v)?,
+                    ":mutes" => channel.mute = v.get_usize()? >= 1,
+                    ":solos" => channel.solo = v.get_usize()? >= 1,
+                    ":sends" => {
+                        let levels = v.get_list()?;
+
+                        if levels.len() > channel.sends.len() {
+                            return Err(Error::IncorrectPropertyPair(property.into(), v.clone()));
+                        }
+
+                        for (send, level) in channel.sends.iter_mut().zip(levels) {
+                            connect(send, property, level)?;
+                        }
+                    }
+                    _ => return Err(Error::IncorrectPropertyPair(property.into(), value.clone())),
+                }
+            }
+        }
+
+        Ok(Blad::Unit)
+    }
+
+    pub fn get(&self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+        let property = list[0].get_atom()?;
+
+        match property {
+            ":output" | ":output_left" => {
+                Ok(Blad::Screech(Screech::Signal(self.output_left.signal())))
+            }
+            ":output_right" => Ok(Blad::Screech(Screech::Signal(self.output_right.signal()))),
+            ":sends" => {
+                let signals = self
+                    .sends
+                    .iter()
+                    .map(|s| Blad::Screech(Screech::Signal(s.signal())))
+                    .collect();
+
+                Ok(Blad::List(signals))
+            }
+            _ => Err(Error::InvalidProperty(property.into())),
+        }
+    }
+}
+
+/// Connect a channel input like `set_input` does, `()` disconnects
+fn connect(input: &mut Signal, property: &str, value: &Blad) -> Result<(), Error> {
+    match value {
+        Blad::Unit => *input = Signal::None,
+        _ => {
+            set_input(Some(input), property, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Mixer {
+    fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
+        self.channels.iter().all(|c| {
+            patchbay.check(c.input)
+                && patchbay.check(c.gain)
+                && patchbay.check(c.pan)
+                && c.sends.iter().all(|s| patchbay.check(*s))
+        })
+    }
+
+    fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
+        let soloing = self.channels.iter().any(|c| c.solo);
+        let audible = self
+            .channels
+            .iter()
+            .filter(|c| !c.mute && (c.solo || !soloing));
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+
+        for channel in audible.clone() {
+            let signal = patchbay.get(channel.input) * patchbay.get(channel.gain);
+
+            // Sweep a quarter circle so the power stays the same across
+            let angle = (patchbay.get(channel.pan).clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+            left += signal * angle.cos();
+            right += signal * angle.sin();
+        }
+
+        for (i, point) in self.sends.iter_mut().enumerate() {
+            let value = audible
+                .clone()
+                .map(|c| patchbay.get(c.input) * patchbay.get(c.gain) * patchbay.get(c.sends[i]))
+                .sum();
+
+            patchbay.set(point, value);
+        }
+
+        patchbay.set(&mut self.output_left, left);
+        patchbay.set(&mut self.output_right, right);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+    use crate::{Blad, Error};
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn pan_and_solo() {
+        let mut engine = new_engine();
+
+        let mixer = insert(&mut engine, ":mixer", "mixer", vec![]);
+        set(
+            &mut engine,
+            &mixer,
+            vec![
+                (":inputs", Blad::List(vec![signal(1.0), signal(0.5)])),
+                (":gains", Blad::List(vec![float(0.5), float(1.0)])),
+                (":pans", Blad::List(vec![float(-1.0), float(0.0)])),
+                (":sends", Blad::List(vec![Blad::List(vec![float(1.0)])])),
+            ],
+        );
+
+        let sends = get(&mut engine, &mixer, ":sends");
+        connect(&mut engine, &mixer, ":output_left", ":output_left");
+        message(
+            &mut engine,
+            vec![atom(":output_right"), sends.get_list().unwrap()[0].clone()],
+        );
+
+        // Fully left, plus the centered channel at equal power on both sides
+        let (left, send) = engine.next_samples();
+        assert!((left - (0.5 + 0.5 * FRAC_1_SQRT_2)).abs() < 1e-6);
+        assert!((send - 0.5).abs() < 1e-6);
+
+        set(
+            &mut engine,
+            &mixer,
+            vec![(":solos", Blad::List(vec![int(0), int(1)]))],
+        );
+        let (left, send) = engine.next_samples();
+        assert!((left - 0.5 * FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(send, 0.0);
+
+        set(
+            &mut engine,
+            &mixer,
+            vec![(":mutes", Blad::List(vec![int(0), int(1)]))],
+        );
+        assert_eq!(engine.next_samples(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn whole_numbers() {
+        let mut engine = new_engine();
+
+        let mixer = insert(&mut engine, ":mixer", "mixer", vec![]);
+        set(
+            &mut engine,
+            &mixer,
+            vec![
+                (":inputs", Blad::List(vec![int(1)])),
+                (":gains", Blad::List(vec![int(1)])),
+                (":pans", Blad::List(vec![int(-1)])),
+            ],
+        );
+        connect(&mut engine, &mixer, ":output_left", ":output_left");
+
+        assert!((engine.next_samples().0 - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn size() {
+        let mut engine = new_engine();
+
+        let mixer = insert(&mut engine, ":mixer", "mixer", vec![int(2), int(3)]);
+        let sends = get(&mut engine, &mixer, ":sends");
+        assert_eq!(sends.get_list().unwrap().len(), 3);
+
+        // Only two channels to connect
+        let inputs = Blad::List(vec![float(1.0), float(1.0), float(1.0)]);
+        let inputs = Blad::List(vec![atom(":inputs"), inputs]);
+        assert!(try_message(&mut engine, vec![atom(":set"), mixer, inputs]).is_err());
+    }
+
+    #[test]
+    fn options_changed() {
+        let mut engine = new_engine();
+
+        let mixer = insert(&mut engine, ":mixer", "mixer", vec![int(2), int(3)]);
+        assert_eq!(
+            insert(&mut engine, ":mixer", "mixer", vec![int(2), int(3)]),
+            mixer
+        );
+
+        // More channels or sends would need more patch points
+        for options in [vec![int(4), int(3)], vec![int(2), int(1)], vec![]] {
+            let mut message = vec![atom(":insert_module"), atom(":mixer"), string("mixer")];
+            message.extend(options);
+
+            assert_eq!(
+                try_message(&mut engine, message),
+                Err(Error::ModuleOptionsChanged("mixer".into()))
+            );
+        }
+    }
+}
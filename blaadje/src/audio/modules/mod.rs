@@ -1,15 +1,48 @@
 mod clock;
+mod delay;
+mod envelope;
 mod filter;
 mod midi;
+mod mixer;
 mod oscillator;
+mod reverb;
 mod sample;
 mod sequencer;
 mod vca;
 
+use crate::{Blad, Error, Literal, Screech};
+use screech::Signal;
+
 pub use clock::Clock;
+pub use delay::Delay;
+pub use envelope::Envelope;
 pub use filter::Filter;
 pub use midi::Midi;
+pub use mixer::Mixer;
 pub use oscillator::Oscillator;
-pub use sample::Sample;
+pub use reverb::Reverb;
+pub use sample::{ChokeGroups, Sample};
 pub use sequencer::Sequencer;
 pub use vca::Vca;
+
+/// Connect an input that takes either a signal or a fixed value, `input` is
+/// `None` when the module has no input named `property`
+fn set_input(input: Option<&mut Signal>, property: &str, value: &Blad) -> Result<Blad, Error> {
+    let signal = match value {
+        Blad::Screech(Screech::Signal(signal)) => Some(*signal),
+        Blad::Literal(Literal::F32(f)) => Some(Signal::Fixed(*f)),
+        Blad::Literal(Literal::I64(i)) => Some(Signal::Fixed(*i as f32)),
+        _ => None,
+    };
+
+    match (input, signal) {
+        (Some(input), Some(signal)) => {
+            *input = signal;
+            Ok(Blad::Unit)
+        }
+        _ => Err(Error::IncorrectPropertyPair(
+            property.to_string(),
+            value.clone(),
+        )),
+    }
+}
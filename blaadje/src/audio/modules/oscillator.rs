@@ -1,39 +1,58 @@
+use super::set_input;
 use crate::core::args_min;
-use crate::{Blad, Error, Literal, Screech};
+use crate::{Blad, Error, Screech};
 use screech::{Module, PatchPoint, Patchbay, Signal};
-
-const PI: f32 = 3.141;
+use std::f32::consts::PI;
 
 enum Waveform {
     Sine,
     Saw,
     Triangle,
-    Pulse(f32),
+    Pulse,
 }
 
 pub struct Oscillator {
     wave_shape: Waveform,
     frequency: Signal,
     amplitude: Signal,
+    pulse_width: Signal,
+    /// Offset added to the phase, a full cycle is 1.0
+    phase: Signal,
+    /// Restarts the cycle on a rising edge, for hard sync
+    sync: Signal,
+    last_sync: f32,
     output: PatchPoint,
     value: f32,
+    sample_rate: f32,
 }
 
 impl Oscillator {
-    pub fn new(output: PatchPoint) -> Self {
+    pub fn new(output: PatchPoint, sample_rate: f32) -> Self {
         Oscillator {
             wave_shape: Waveform::Sine,
             frequency: Signal::Fixed(220.0),
             amplitude: Signal::Fixed(0.1),
+            pulse_width: Signal::Fixed(0.5),
+            phase: Signal::Fixed(0.0),
+            sync: Signal::None,
+            last_sync: 0.0,
             output,
             value: 0.0,
+            sample_rate,
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
     pub fn reset(&mut self) {
         self.wave_shape = Waveform::Sine;
         self.frequency = Signal::Fixed(220.0);
         self.amplitude = Signal::Fixed(0.1);
+        self.pulse_width = Signal::Fixed(0.5);
+        self.phase = Signal::Fixed(0.0);
+        self.sync = Signal::None;
     }
 
     pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
@@ -45,25 +64,9 @@ impl Oscillator {
             let value = &pair[1];
 
             match (property, value) {
-                (":frequency", Blad::Literal(Literal::F32(f))) => {
-                    self.frequency = Signal::Fixed(*f);
-                    Ok(Blad::Unit)
-                }
-                (":frequency", Blad::Screech(Screech::Signal(signal))) => {
-                    self.frequency = *signal;
-                    Ok(Blad::Unit)
-                }
-                (":amplitude", Blad::Literal(Literal::F32(f))) => {
-                    self.amplitude = Signal::Fixed(*f);
-                    Ok(Blad::Unit)
-                }
-                (":amplitude", Blad::Screech(Screech::Signal(signal))) => {
-                    self.amplitude = *signal;
-                    Ok(Blad::Unit)
-                }
                 (":waveshape", Blad::Atom(string)) => {
                     match string.as_ref() {
-                        ":pulse" => self.wave_shape = Waveform::Pulse(0.5),
+                        ":pulse" => self.wave_shape = Waveform::Pulse,
                         ":sine" => self.wave_shape = Waveform::Sine,
                         ":triangle" => self.wave_shape = Waveform::Triangle,
                         ":saw" => self.wave_shape = Waveform::Saw,
@@ -71,7 +74,7 @@ impl Oscillator {
                     };
                     Ok(Blad::Unit)
                 }
-                (a, b) => Err(Error::IncorrectPropertyPair(a.to_string(), b.clone())),
+                _ => set_input(self.input(property), property, value),
             }?;
         }
 
@@ -87,28 +90,65 @@ impl Oscillator {
             _ => Err(Error::InvalidProperty(property.into())),
         }
     }
+
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":frequency" => Some(&mut self.frequency),
+            ":amplitude" => Some(&mut self.amplitude),
+            ":pulse_width" => Some(&mut self.pulse_width),
+            ":phase" => Some(&mut self.phase),
+            ":sync" => Some(&mut self.sync),
+            _ => None,
+        }
+    }
 }
 
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Oscillator {
     fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
-        patchbay.check(self.frequency) && patchbay.check(self.amplitude)
+        patchbay.check(self.frequency)
+            && patchbay.check(self.amplitude)
+            && patchbay.check(self.pulse_width)
+            && patchbay.check(self.phase)
+            && patchbay.check(self.sync)
     }
 
     fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
-        // Ramp up from -1.0 to 1.0 based on the set `frequency`
+        let sync = patchbay.get(self.sync);
+
+        if sync > 0.0 && self.last_sync <= 0.0 {
+            self.value = -1.0;
+        }
+
+        self.last_sync = sync;
+
+        // Ramp up from -1.0 to 1.0 once per cycle based on the set `frequency`
         // then use this value to convert to the specific waveforms
-        self.value += (1.0 / SAMPLE_RATE as f32) * patchbay.get(self.frequency);
-        // Wrap around
-        if self.value >= 1.0 {
-            self.value -= 2.0;
+        let step = patchbay.get(self.frequency) / self.sample_rate;
+        self.value += 2.0 * step;
+        // Wrap around, negative frequencies run through the cycle backwards
+        if !(-1.0..1.0).contains(&self.value) {
+            self.value = (self.value + 1.0).rem_euclid(2.0) - 1.0;
         }
 
+        // Shift by the phase offset, keeping the same range
+        let offset = patchbay.get(self.phase);
+        let value = if offset == 0.0 {
+            self.value
+        } else {
+            (self.value + 2.0 * offset + 1.0).rem_euclid(2.0) - 1.0
+        };
+
+        // Position in the cycle from 0.0 to 1.0, the correction around
+        // discontinuities needs the step in the same unit
+        let t = (value + 1.0) * 0.5;
+        let dt = step.abs().min(0.5);
+
         // Create the desired waveform
         let wave = match self.wave_shape {
-            Waveform::Saw => self.value,
-            Waveform::Sine => sine(self.value),
-            Waveform::Triangle => triangle(self.value),
-            Waveform::Pulse(duty_cycle) => pulse(self.value, duty_cycle),
+            Waveform::Saw => value - poly_blep(t, dt),
+            Waveform::Sine => (PI * value).sin(),
+            Waveform::Triangle => triangle(t, dt),
+            Waveform::Pulse => pulse(t, dt, patchbay.get(self.pulse_width)),
         };
 
         // Set the amplitude
@@ -119,37 +159,109 @@ impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Oscillator {
     }
 }
 
-// Bashkara approximation of a sine
-fn sine(input: f32) -> f32 {
-    // Calculate with positive values only
-    let x = if input < 0.0 { -input * PI } else { input * PI };
+fn pulse(t: f32, dt: f32, width: f32) -> f32 {
+    // Keep both halves at least a step wide so the pulse doesn't vanish
+    let width = width.clamp(dt, 1.0 - dt);
+    let naive = if t < width { 1.0 } else { -1.0 };
+
+    naive + poly_blep(t, dt) - poly_blep((t + 1.0 - width).rem_euclid(1.0), dt)
+}
+
+fn triangle(t: f32, dt: f32) -> f32 {
+    let naive = 1.0 - 4.0 * (t - 0.5).abs();
 
-    let numerator = 16.0 * x * (PI - x);
-    let denominator = 5.0 * PI * PI - 4.0 * x * (PI - x);
-    let sine = numerator / denominator;
+    naive + 4.0 * dt * (poly_blamp(t, dt) - poly_blamp((t + 0.5).rem_euclid(1.0), dt))
+}
 
-    // Normalize back positive to negative if needed
-    if input < 0.0 {
-        -sine
+/// Correction for a jump of 2.0 at the start of the cycle, smoothed over the
+/// samples around it to remove most of the aliasing of a naive jump.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
     } else {
-        sine
+        0.0
     }
 }
-fn triangle(input: f32) -> f32 {
-    if input < 0.0 {
-        (input + 1.0) * 2.0 - 1.0
+
+/// Integrated `poly_blep`, the correction for a change in slope instead of a
+/// jump, used for the corners of the triangle.
+fn poly_blamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt - 1.0;
+        -t * t * t / 3.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        t * t * t / 3.0
     } else {
-        (input * 2.0) * -1.0 + 1.0
+        0.0
     }
 }
 
-fn pulse(input: f32, duty_cycle: f32) -> f32 {
-    // Normalize around the centerpoint
-    let threshold = (duty_cycle * 2.0) - 1.0;
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
 
-    if input >= threshold {
-        1.0
-    } else {
-        -1.0
+    #[test]
+    fn output() {
+        let mut engine = new_engine();
+
+        let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+        connect(&mut engine, &osc, ":output", ":output_left");
+
+        let samples: Vec<(f32, f32)> = (0..100).map(|_| engine.next_samples()).collect();
+
+        assert!(samples.iter().any(|(l, _)| *l != 0.0));
+        assert!(samples.iter().all(|(_, r)| *r == 0.0));
+    }
+
+    #[test]
+    fn sine() {
+        let mut engine = new_engine();
+
+        let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+        set(
+            &mut engine,
+            &osc,
+            vec![(":frequency", float(11_025.0)), (":amplitude", float(1.0))],
+        );
+        connect(&mut engine, &osc, ":output", ":output_left");
+
+        // A quarter of the sample rate takes four samples per cycle
+        for expected in [1.0, 0.0, -1.0, 0.0, 1.0] {
+            assert!((engine.next_samples().0 - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn negative_frequency() {
+        let mut engine = new_engine();
+
+        let osc = insert(&mut engine, ":oscillator", "osc", vec![]);
+        set(
+            &mut engine,
+            &osc,
+            vec![(":frequency", int(-11_025)), (":amplitude", int(1))],
+        );
+        connect(&mut engine, &osc, ":output", ":output_left");
+
+        // The same cycle played backwards
+        for expected in [-1.0, 0.0, 1.0, 0.0, -1.0] {
+            assert!((engine.next_samples().0 - expected).abs() < 1e-5);
+        }
+
+        // Keeps wrapping around instead of running off
+        set(
+            &mut engine,
+            &osc,
+            vec![
+                (":frequency", float(-1_000.0)),
+                (":waveshape", atom(":triangle")),
+            ],
+        );
+        assert!((0..1_000).all(|_| engine.next_samples().0.abs() <= 1.01));
     }
 }
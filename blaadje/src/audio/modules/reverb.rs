@@ -0,0 +1,239 @@
+use super::set_input;
+use crate::core::args_min;
+use crate::{Blad, Error, Screech};
+use screech::{Module, PatchPoint, Patchbay, Signal};
+
+/// Comb filter lengths in frames at 44.1kHz, from Freeverb
+const COMBS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+
+/// Allpass filter lengths in frames at 44.1kHz, from Freeverb
+const ALLPASSES: [usize; 4] = [556, 441, 341, 225];
+
+/// Extra length for the right channel to decorrelate it from the left
+const STEREO_SPREAD: usize = 23;
+
+/// Scales the input down to keep the sum of the combs in range
+const INPUT_GAIN: f32 = 0.015;
+
+/// Lowpass filtered feedback comb, sets the length and color of the tail
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    filter: f32,
+}
+
+impl Comb {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            position: 0,
+            filter: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.position];
+
+        self.filter = output * (1.0 - damping) + self.filter * damping;
+        self.buffer[self.position] = input + self.filter * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        output
+    }
+}
+
+/// Spreads the echoes of the combs out in time without coloring them
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Allpass {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+
+        self.buffer[self.position] = input + delayed * 0.5;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        delayed - input
+    }
+}
+
+/// One channel of parallel combs into allpasses in series
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(spread: usize, sample_rate: f32) -> Self {
+        let scale = |length: usize| ((length + spread) as f32 * sample_rate / 44_100.0) as usize;
+
+        Self {
+            combs: COMBS.iter().map(|l| Comb::new(scale(*l))).collect(),
+            allpasses: ALLPASSES.iter().map(|l| Allpass::new(scale(*l))).collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let combined = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(input, feedback, damping))
+            .sum();
+
+        self.allpasses
+            .iter_mut()
+            .fold(combined, |signal, allpass| allpass.process(signal))
+    }
+}
+
+/// Stereo Freeverb style reverb, both inputs are mixed into one tank per side
+pub struct Reverb {
+    input_left: Signal,
+    input_right: Signal,
+    size: Signal,
+    damping: Signal,
+    mix: Signal,
+    output_left: PatchPoint,
+    output_right: PatchPoint,
+    left: Tank,
+    right: Tank,
+}
+
+impl Reverb {
+    pub fn new(output_left: PatchPoint, output_right: PatchPoint, sample_rate: f32) -> Self {
+        Self {
+            input_left: Signal::None,
+            input_right: Signal::None,
+            size: Signal::Fixed(0.5),
+            damping: Signal::Fixed(0.5),
+            mix: Signal::Fixed(0.3),
+            output_left,
+            output_right,
+            left: Tank::new(0, sample_rate),
+            right: Tank::new(STEREO_SPREAD, sample_rate),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.left = Tank::new(0, sample_rate);
+        self.right = Tank::new(STEREO_SPREAD, sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.input_left = Signal::None;
+        self.input_right = Signal::None;
+        self.size = Signal::Fixed(0.5);
+        self.damping = Signal::Fixed(0.5);
+        self.mix = Signal::Fixed(0.3);
+    }
+
+    pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+
+        for b in list.iter() {
+            let pair = b.get_list()?;
+            let property = pair[0].get_atom()?;
+            let value = &pair[1];
+
+            set_input(self.input(property), property, value)?;
+        }
+
+        Ok(Blad::Unit)
+    }
+
+    pub fn get(&self, list: &[Blad]) -> Result<Blad, Error> {
+        args_min(list, 1)?;
+        let property = list[0].get_atom()?;
+
+        match property {
+            ":output" | ":output_left" => {
+                Ok(Blad::Screech(Screech::Signal(self.output_left.signal())))
+            }
+            ":output_right" => Ok(Blad::Screech(Screech::Signal(self.output_right.signal()))),
+            _ => Err(Error::InvalidProperty(property.into())),
+        }
+    }
+
+    /// The input a property connects to
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":input_left" => Some(&mut self.input_left),
+            ":input_right" => Some(&mut self.input_right),
+            ":size" => Some(&mut self.size),
+            ":damping" => Some(&mut self.damping),
+            ":mix" => Some(&mut self.mix),
+            _ => None,
+        }
+    }
+}
+
+impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Reverb {
+    fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
+        patchbay.check(self.input_left)
+            && patchbay.check(self.input_right)
+            && patchbay.check(self.size)
+            && patchbay.check(self.damping)
+            && patchbay.check(self.mix)
+    }
+
+    fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
+        let left = patchbay.get(self.input_left);
+        let right = patchbay.get(self.input_right);
+
+        // Same ranges as Freeverb, larger rooms ring longer
+        let feedback = 0.7 + 0.28 * patchbay.get(self.size).clamp(0.0, 1.0);
+        let damping = 0.4 * patchbay.get(self.damping).clamp(0.0, 1.0);
+        let mix = patchbay.get(self.mix).clamp(0.0, 1.0);
+
+        let input = (left + right) * INPUT_GAIN;
+        let wet_left = self.left.process(input, feedback, damping);
+        let wet_right = self.right.process(input, feedback, damping);
+
+        patchbay.set(&mut self.output_left, left * (1.0 - mix) + wet_left * mix);
+        patchbay.set(
+            &mut self.output_right,
+            right * (1.0 - mix) + wet_right * mix,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+
+    #[test]
+    fn tail() {
+        let mut engine = new_engine();
+
+        let sample = sample(&mut engine, "sample", vec![]);
+        let input = get(&mut engine, &sample, ":output");
+        let reverb = insert(&mut engine, ":reverb", "reverb", vec![]);
+        set(
+            &mut engine,
+            &reverb,
+            vec![(":input_left", input), (":mix", float(1.0))],
+        );
+        connect(&mut engine, &reverb, ":output_left", ":output_left");
+        connect(&mut engine, &reverb, ":output_right", ":output_right");
+
+        let frames: Vec<(f32, f32)> = (0..100_000).map(|_| engine.next_samples()).collect();
+
+        // Both sides ring on after the short input, differently, then die out
+        assert!(frames[2_000..4_000]
+            .iter()
+            .any(|(l, r)| l.abs() > 1e-4 && l != r));
+        assert!(frames.iter().all(|(l, r)| l.abs() < 1.0 && r.abs() < 1.0));
+        assert!(frames[99_000..]
+            .iter()
+            .all(|(l, r)| l.abs() < 1e-5 && r.abs() < 1e-5));
+    }
+}
@@ -1,36 +1,132 @@
+use super::set_input;
 use crate::core::args_min;
-use crate::{Blad, Error, Screech};
+use crate::{Blad, Buffer, Error, Literal, Screech};
 use screech::{Module, PatchPoint, Patchbay, Signal};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Number of choke groups a sample can be put in
+pub const CHOKE_GROUPS: usize = 16;
+
+/// Keeps track of the sample triggered last in every choke group, shared by
+/// all samples of an engine. Triggering a sample cuts off the others in its
+/// group, like an open hi-hat cut by a closed one.
+pub struct ChokeGroups {
+    next_id: AtomicUsize,
+    groups: [AtomicUsize; CHOKE_GROUPS],
+}
+
+impl ChokeGroups {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicUsize::new(1),
+            groups: std::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn trigger(&self, group: usize, id: usize) {
+        self.groups[group].store(id, Ordering::Relaxed);
+    }
+
+    fn is_choked(&self, group: usize, id: usize) -> bool {
+        self.groups[group].load(Ordering::Relaxed) != id
+    }
+}
 
 enum Mode {
     OneShot,
     Loop,
+    /// Play only while the trigger is held
+    Gate,
+}
+
+enum Interpolation {
+    None,
+    Linear,
+    Cubic,
 }
 
 pub struct Sample {
+    id: usize,
     trigger: Signal,
-    output: PatchPoint,
-    samples: Vec<f32>,
-    position: usize,
+    /// Trigger value of the previous frame, playback starts on a rising edge
+    last_trigger: f32,
+    /// Sampled when triggered, so it acts as velocity
+    gain: Signal,
+    velocity: f32,
+    choke: Option<usize>,
+    choke_groups: Arc<ChokeGroups>,
+    rate: Signal,
+    pitch: Signal,
+    output_left: PatchPoint,
+    output_right: PatchPoint,
+    /// Buffers keep the rate they were recorded at and are stepped through at
+    /// their ratio to the engine rate, interpolating in between frames
+    samples: Buffer,
+    sample_rate: f32,
+    /// Position in frames, in between frames when not playing at rate 1
+    position: f64,
     mode: Mode,
+    interpolation: Interpolation,
+    start: usize,
+    end: Option<usize>,
+    loop_start: Option<usize>,
+    loop_end: Option<usize>,
     active: bool,
 }
 
 impl Sample {
-    pub fn new(output: PatchPoint) -> Self {
+    pub fn new(
+        output_left: PatchPoint,
+        output_right: PatchPoint,
+        choke_groups: Arc<ChokeGroups>,
+        sample_rate: f32,
+    ) -> Self {
         Self {
+            id: choke_groups.id(),
             trigger: Signal::None,
-            output,
-            position: 0,
-            samples: Vec::new(),
+            last_trigger: 0.0,
+            gain: Signal::Fixed(1.0),
+            velocity: 0.0,
+            choke: None,
+            choke_groups,
+            rate: Signal::Fixed(1.0),
+            pitch: Signal::Fixed(0.0),
+            output_left,
+            output_right,
+            position: 0.0,
+            samples: Buffer::new(Vec::new().into(), 0, 1),
+            sample_rate,
             mode: Mode::OneShot,
+            interpolation: Interpolation::Linear,
+            start: 0,
+            end: None,
+            loop_start: None,
+            loop_end: None,
             active: false,
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
     pub fn reset(&mut self) {
         self.trigger = Signal::None;
+        self.gain = Signal::Fixed(1.0);
+        self.choke = None;
+        self.rate = Signal::Fixed(1.0);
+        self.pitch = Signal::Fixed(0.0);
         self.mode = Mode::OneShot;
+        self.interpolation = Interpolation::Linear;
+        self.start = 0;
+        self.end = None;
+        self.loop_start = None;
+        self.loop_end = None;
     }
 
     pub fn set(&mut self, list: &[Blad]) -> Result<Blad, Error> {
@@ -42,30 +138,68 @@ impl Sample {
             let value = &pair[1];
 
             match (property, value) {
-                (":trigger", Blad::Screech(Screech::Signal(signal))) => {
-                    self.trigger = *signal;
+                (":choke", Blad::Unit) => {
+                    self.choke = None;
+                    Ok(Blad::Unit)
+                }
+                (":choke", Blad::Literal(Literal::I64(group)))
+                    if (0..CHOKE_GROUPS as i64).contains(group) =>
+                {
+                    self.choke = Some(*group as usize);
                     Ok(Blad::Unit)
                 }
-                (":samples", Blad::List(s)) => {
+                (":samples", Blad::Buffer(buffer)) => {
+                    // Only the reference is copied, the samples are shared
+                    self.samples = buffer.clone();
+
+                    Ok(Blad::Unit)
+                }
+                (":samples", Blad::List(list)) => {
                     let mut samples = vec![];
 
-                    for sample in s {
+                    for sample in list {
                         samples.push(sample.get_f32()?);
                     }
 
-                    self.samples = samples;
+                    // A plain list has no sample rate, it plays at the engine rate
+                    self.samples = Buffer::new(samples.into(), 0, 1);
 
                     Ok(Blad::Unit)
                 }
+                (":start", frame) => {
+                    self.start = frame.get_usize()?;
+                    Ok(Blad::Unit)
+                }
+                (":end", frame) => {
+                    self.end = Some(frame.get_usize()?);
+                    Ok(Blad::Unit)
+                }
+                (":loop_start", frame) => {
+                    self.loop_start = Some(frame.get_usize()?);
+                    Ok(Blad::Unit)
+                }
+                (":loop_end", frame) => {
+                    self.loop_end = Some(frame.get_usize()?);
+                    Ok(Blad::Unit)
+                }
                 (":mode", Blad::Atom(string)) => {
                     match string.as_ref() {
                         ":oneshot" => self.mode = Mode::OneShot,
                         ":loop" => self.mode = Mode::Loop,
+                        ":gate" => self.mode = Mode::Gate,
                         _ => self.mode = Mode::OneShot,
                     };
                     Ok(Blad::Unit)
                 }
-                (a, b) => Err(Error::IncorrectPropertyPair(a.to_string(), b.clone())),
+                (":interpolation", Blad::Atom(string)) => {
+                    match string.as_ref() {
+                        ":none" => self.interpolation = Interpolation::None,
+                        ":cubic" => self.interpolation = Interpolation::Cubic,
+                        _ => self.interpolation = Interpolation::Linear,
+                    };
+                    Ok(Blad::Unit)
+                }
+                _ => set_input(self.input(property), property, value),
             }?;
         }
 
@@ -77,42 +211,250 @@ impl Sample {
         let property = list[0].get_atom()?;
 
         match property {
-            ":output" => Ok(Blad::Screech(Screech::Signal(self.output.signal()))),
+            ":output" | ":output_left" => {
+                Ok(Blad::Screech(Screech::Signal(self.output_left.signal())))
+            }
+            ":output_right" => Ok(Blad::Screech(Screech::Signal(self.output_right.signal()))),
             _ => Err(Error::InvalidProperty(property.into())),
         }
     }
+
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":trigger" => Some(&mut self.trigger),
+            ":gain" => Some(&mut self.gain),
+            ":rate" => Some(&mut self.rate),
+            ":pitch" => Some(&mut self.pitch),
+            _ => None,
+        }
+    }
+
+    /// The playable region and the looping region within it, in frames
+    fn regions(&self) -> ((f64, f64), (f64, f64)) {
+        let frames = self.samples.frames();
+        let end = self.end.unwrap_or(frames).min(frames);
+        let start = self.start.min(end);
+        let loop_end = self.loop_end.unwrap_or(end).clamp(start, end);
+        let loop_start = self.loop_start.unwrap_or(start).clamp(start, loop_end);
+
+        (
+            (start as f64, end as f64),
+            (loop_start as f64, loop_end as f64),
+        )
+    }
+
+    /// Frames of the buffer to advance for every frame of the engine
+    fn speed(&self) -> f64 {
+        match self.samples.sample_rate {
+            0 => 1.0,
+            sample_rate => sample_rate as f64 / self.sample_rate as f64,
+        }
+    }
+
+    /// Read a channel at a position in between frames
+    fn read(&self, position: f64, channel: usize) -> f32 {
+        let frame = position.floor();
+        let fraction = (position - frame) as f32;
+        let frame = frame as isize;
+
+        let at = |offset: isize| match usize::try_from(frame + offset) {
+            Ok(i) => self.samples.get(i, channel),
+            Err(_) => 0.0,
+        };
+
+        match self.interpolation {
+            Interpolation::None => at(0),
+            Interpolation::Linear => at(0) + (at(1) - at(0)) * fraction,
+            Interpolation::Cubic => hermite(at(-1), at(0), at(1), at(2), fraction),
+        }
+    }
 }
 
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Sample {
     fn is_ready<const POINTS: usize>(&self, patchbay: &Patchbay<POINTS>) -> bool {
         patchbay.check(self.trigger)
+            && patchbay.check(self.gain)
+            && patchbay.check(self.rate)
+            && patchbay.check(self.pitch)
     }
 
     fn process<const P: usize>(&mut self, patchbay: &mut Patchbay<P>) {
-        if patchbay.get(self.trigger) > 0.0 {
-            self.position = 0;
+        let ((start, end), (loop_start, loop_end)) = self.regions();
+        let semitones = patchbay.get(self.pitch) as f64 / 12.0;
+        let step = patchbay.get(self.rate) as f64 * semitones.exp2() * self.speed();
+        let reverse = step < 0.0;
+
+        let trigger = patchbay.get(self.trigger);
+        let pressed = trigger > 0.0 && self.last_trigger <= 0.0;
+        let released = trigger <= 0.0 && self.last_trigger > 0.0;
+        self.last_trigger = trigger;
+
+        if pressed {
+            // Playing backwards starts at the end
+            self.position = if reverse { end - 1.0 } else { start };
+            self.velocity = trigger * patchbay.get(self.gain);
             self.active = true;
+
+            if let Some(group) = self.choke {
+                self.choke_groups.trigger(group, self.id);
+            }
         }
 
-        if self.position >= self.samples.len() {
-            self.position = 0;
+        if released && matches!(self.mode, Mode::Gate) {
+            self.active = false;
+        }
 
-            self.active = match self.mode {
-                Mode::Loop => true,
-                Mode::OneShot => false,
+        if let Some(group) = self.choke {
+            if self.choke_groups.is_choked(group, self.id) {
+                self.active = false;
             }
-        };
+        }
+
+        let looping = matches!(self.mode, Mode::Loop) && loop_end > loop_start;
+        let length = loop_end - loop_start;
+
+        if looping && !reverse && self.position >= loop_end {
+            self.position = loop_start + (self.position - loop_end) % length;
+        } else if looping && reverse && self.position < loop_start {
+            self.position = loop_end - (loop_start - self.position) % length;
+        } else if self.position >= end || self.position < start {
+            self.active = false;
+        }
 
-        let output = match (self.samples.len(), self.active) {
-            (0, _) => 0.0,
-            (_, true) => {
-                let sample = self.samples[self.position];
-                self.position += 1;
-                sample
+        let (left, right) = match (end > start, self.active) {
+            (true, true) => {
+                // Mono samples play on both sides
+                let left = self.read(self.position, 0) * self.velocity;
+                let right = self.read(self.position, 1) * self.velocity;
+                self.position += step;
+                (left, right)
             }
-            _ => 0.0,
+            _ => (0.0, 0.0),
         };
 
-        patchbay.set(&mut self.output, output);
+        patchbay.set(&mut self.output_left, left);
+        patchbay.set(&mut self.output_right, right);
+    }
+}
+
+/// Cubic Hermite interpolation between `b` and `c`
+fn hermite(a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    let c0 = b;
+    let c1 = 0.5 * (c - a);
+    let c2 = a - 2.5 * b + 2.0 * c - 0.5 * d;
+    let c3 = 0.5 * (d - a) + 1.5 * (b - c);
+
+    ((c3 * t + c2) * t + c1) * t + c0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::engine::tests::*;
+    use crate::{Blad, Buffer};
+
+    #[test]
+    fn velocity() {
+        let mut engine = new_engine();
+
+        let sample = sample(&mut engine, "sample", vec![(":gain", float(0.5))]);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        // The held trigger only starts playback once
+        assert_eq!(left(&mut engine, 6), vec![0.1, 0.2, 0.3, 0.4, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn whole_gain() {
+        let mut engine = new_engine();
+
+        let sample = sample(&mut engine, "sample", vec![(":gain", int(2))]);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        assert_eq!(left(&mut engine, 2), vec![0.4, 0.8]);
+    }
+
+    #[test]
+    fn list() {
+        let mut engine = new_engine();
+
+        let samples = Blad::List(vec![float(0.5), float(-0.5)]);
+        let sample = sample(&mut engine, "sample", vec![(":samples", samples)]);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        assert_eq!(left(&mut engine, 3), vec![0.5, -0.5, 0.0]);
+    }
+
+    #[test]
+    fn choke() {
+        let mut engine = new_engine();
+
+        let open = sample(&mut engine, "open", vec![(":choke", int(1))]);
+        connect(&mut engine, &open, ":output", ":output_left");
+
+        assert_eq!(left(&mut engine, 2), vec![0.2, 0.4]);
+
+        // The closed hat is triggered later and cuts off the open one
+        let closed = sample(&mut engine, "closed", vec![(":choke", int(1))]);
+        connect(&mut engine, &closed, ":output", ":output_right");
+
+        engine.next_samples();
+        assert_eq!(engine.next_samples(), (0.0, 0.4));
+    }
+
+    #[test]
+    fn reverse() {
+        let mut engine = new_engine();
+
+        let sample = sample(&mut engine, "sample", vec![(":rate", int(-1))]);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        assert_eq!(left(&mut engine, 5), vec![0.8, 0.6, 0.4, 0.2, 0.0]);
+    }
+
+    #[test]
+    fn pitch() {
+        let mut engine = new_engine();
+
+        // An octave up skips every other frame
+        let sample = sample(&mut engine, "sample", vec![(":pitch", int(12))]);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        assert_eq!(left(&mut engine, 3), vec![0.2, 0.6, 0.0]);
+    }
+
+    #[test]
+    fn looping() {
+        let mut engine = new_engine();
+
+        let properties = vec![
+            (":mode", atom(":loop")),
+            (":loop_start", int(1)),
+            (":loop_end", int(3)),
+        ];
+        let sample = sample(&mut engine, "sample", properties);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        assert_eq!(
+            left(&mut engine, 7),
+            vec![0.2, 0.4, 0.6, 0.4, 0.6, 0.4, 0.6]
+        );
+    }
+
+    #[test]
+    fn source_rate() {
+        let mut engine = new_engine();
+
+        // A ramp recorded at 48kHz, played by an engine at 44.1kHz
+        let ramp: Vec<f32> = (1..=470).map(|i| i as f32 / 470.0).collect();
+        let samples = Blad::Buffer(Buffer::new(ramp.into(), 48_000, 1));
+        let sample = sample(&mut engine, "sample", vec![(":samples", samples)]);
+        connect(&mut engine, &sample, ":output", ":output_left");
+
+        let output = left(&mut engine, 500);
+        let expected = (100.0 * 48_000.0 / 44_100.0 + 1.0) / 470.0;
+
+        // Same pitch and length as the recording, so fewer frames
+        assert!((output[100] - expected).abs() < 1e-4);
+        assert_eq!(output.iter().filter(|s| **s != 0.0).count(), 432);
     }
 }
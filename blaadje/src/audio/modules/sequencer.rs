@@ -1,3 +1,4 @@
+use super::set_input;
 use crate::core::args_min;
 use crate::{Blad, Error, Screech};
 use screech::{Module, PatchPoint, Patchbay, Signal};
@@ -99,10 +100,6 @@ impl Sequencer {
             let value = &pair[1];
 
             match (property, value) {
-                (":trigger", Blad::Screech(Screech::Signal(signal))) => {
-                    self.trigger = *signal;
-                    Ok(Blad::Unit)
-                }
                 (":frequencies", Blad::List(vs)) => {
                     let mut values = vec![];
 
@@ -137,7 +134,7 @@ impl Sequencer {
 
                     Ok(Blad::Unit)
                 }
-                (a, b) => Err(Error::IncorrectPropertyPair(a.to_string(), b.clone())),
+                _ => set_input(self.input(property), property, value),
             }?;
         }
 
@@ -159,6 +156,13 @@ impl Sequencer {
             _ => Err(Error::InvalidProperty(property.into())),
         }
     }
+
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":trigger" => Some(&mut self.trigger),
+            _ => None,
+        }
+    }
 }
 
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Sequencer {
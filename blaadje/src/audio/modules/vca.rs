@@ -1,5 +1,6 @@
+use super::set_input;
 use crate::core::args_min;
-use crate::{Blad, Error, Literal, Screech};
+use crate::{Blad, Error, Screech};
 use screech::{Module, PatchPoint, Patchbay, Signal};
 
 /// VCA module that takes two inputs (signal and modulator) and has a single output.
@@ -31,21 +32,7 @@ impl Vca {
             let property = pair[0].get_atom()?;
             let value = &pair[1];
 
-            match (property, value) {
-                (":input", Blad::Screech(Screech::Signal(signal))) => {
-                    self.input = *signal;
-                    Ok(Blad::Unit)
-                }
-                (":modulator", Blad::Screech(Screech::Signal(signal))) => {
-                    self.modulator = *signal;
-                    Ok(Blad::Unit)
-                }
-                (":modulator", Blad::Literal(Literal::F32(gain))) => {
-                    self.modulator = Signal::Fixed(*gain);
-                    Ok(Blad::Unit)
-                }
-                (a, b) => Err(Error::IncorrectPropertyPair(a.to_string(), b.clone())),
-            }?;
+            set_input(self.input(property), property, value)?;
         }
 
         Ok(Blad::Unit)
@@ -60,6 +47,14 @@ impl Vca {
             _ => Err(Error::InvalidProperty(property.into())),
         }
     }
+
+    fn input(&mut self, property: &str) -> Option<&mut Signal> {
+        match property {
+            ":input" => Some(&mut self.input),
+            ":modulator" => Some(&mut self.modulator),
+            _ => None,
+        }
+    }
 }
 
 impl<const SAMPLE_RATE: usize> Module<SAMPLE_RATE> for Vca {
@@ -0,0 +1,144 @@
+use super::{System, CHANNELS};
+use crate::{Channel, ChannelReceiver};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+const NULL_HOST: &str = "null";
+const NULL_DEVICE: &str = "null";
+const NULL_MIDI_PORT: &str = "null";
+
+/// Headless system that collects pushed samples in memory instead of sending
+/// them to an audio device, for tests and offline rendering.
+pub struct NullSystem {
+    samples: Arc<Mutex<Vec<f32>>>,
+    capacity: Option<usize>,
+    sample_rate: Option<usize>,
+    midi_channel: Channel,
+    midi_receiver: Option<ChannelReceiver>,
+    midi_input: Option<String>,
+}
+
+impl NullSystem {
+    pub fn new() -> Self {
+        let (midi_channel, midi_receiver) = Channel::new();
+
+        Self {
+            samples: Arc::new(Mutex::new(Vec::new())),
+            capacity: None,
+            sample_rate: None,
+            midi_channel,
+            midi_receiver: Some(midi_receiver),
+            midi_input: None,
+        }
+    }
+
+    /// Report the buffer as full once `capacity` frames have been pushed
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Run at a fixed rate, whatever rate starting the audio asks for
+    pub fn with_sample_rate(mut self, sample_rate: usize) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Queue raw MIDI messages as if they were received from a MIDI input
+    pub fn with_midi(self, messages: &[u32]) -> Self {
+        for message in messages {
+            self.send_midi(*message);
+        }
+
+        self
+    }
+
+    pub fn send_midi(&self, message: u32) {
+        self.midi_channel.send_midi(message);
+    }
+
+    /// Shared handle to the interleaved samples pushed so far
+    pub fn samples(&self) -> Arc<Mutex<Vec<f32>>> {
+        self.samples.clone()
+    }
+
+    pub fn midi_input(&self) -> Option<&str> {
+        self.midi_input.as_deref()
+    }
+}
+
+impl Default for NullSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System for NullSystem {
+    fn get_hosts(&self) -> Vec<String> {
+        vec![NULL_HOST.to_string()]
+    }
+
+    fn get_default_host(&self) -> String {
+        NULL_HOST.to_string()
+    }
+
+    fn get_devices(&self, _host: &str) -> Vec<String> {
+        vec![NULL_DEVICE.to_string()]
+    }
+
+    fn get_default_device(&self, _host: &str) -> String {
+        NULL_DEVICE.to_string()
+    }
+
+    fn start_audio(
+        &mut self,
+        _host_id: &str,
+        _device_id: &str,
+        _buffer_size: usize,
+        sample_rate: usize,
+        _bit_depth: usize,
+    ) -> Result<usize, Box<dyn Error>> {
+        Ok(self.sample_rate.unwrap_or(sample_rate))
+    }
+
+    fn stop_audio(&mut self) {}
+
+    fn is_running(&self) -> bool {
+        // Pushed frames are always collected
+        true
+    }
+
+    fn vacant_frames(&self) -> usize {
+        match self.capacity {
+            Some(capacity) => {
+                let pushed = self.samples.lock().unwrap().len() / CHANNELS;
+                capacity.saturating_sub(pushed)
+            }
+            None => usize::MAX,
+        }
+    }
+
+    fn push_frames(&mut self, frames: &[f32]) {
+        self.samples.lock().unwrap().extend_from_slice(frames);
+    }
+
+    fn take_midi_channel(&mut self) -> Option<ChannelReceiver> {
+        self.midi_receiver.take()
+    }
+
+    fn get_midi_inputs(&self) -> Vec<String> {
+        vec![NULL_MIDI_PORT.to_string()]
+    }
+
+    fn get_midi_outputs(&self) -> Vec<String> {
+        vec![NULL_MIDI_PORT.to_string()]
+    }
+
+    fn connect_midi_input(&mut self, id: &str) {
+        self.midi_input = Some(id.to_string());
+    }
+
+    fn disconnect_midi_input(&mut self) {
+        self.midi_input = None;
+    }
+}
@@ -1,12 +1,12 @@
-use crate::Channel;
+use crate::ChannelReceiver;
 use std::error::Error;
-use std::sync::{Arc, Mutex};
 
 pub trait System {
     fn get_hosts(&self) -> Vec<String>;
     fn get_default_host(&self) -> String;
     fn get_devices(&self, host: &str) -> Vec<String>;
     fn get_default_device(&self, host: &str) -> String;
+    /// Open the audio stream, returning the sample rate it runs at
     fn start_audio(
         &mut self,
         host_id: &str,
@@ -14,11 +14,17 @@ pub trait System {
         buffer_size: usize,
         sample_rate: usize,
         bit_depth: usize,
-    ) -> Result<(), Box<dyn Error>>;
+    ) -> Result<usize, Box<dyn Error>>;
     fn stop_audio(&mut self);
-    fn buffer_full(&self) -> bool;
-    fn push_sample(&mut self, sample: f32);
-    fn get_midi_channel(&self) -> Arc<Mutex<Channel>>;
+    /// Whether a stream is open that frames can be pushed to
+    fn is_running(&self) -> bool;
+    /// Number of frames that can be pushed without overflowing the buffer
+    fn vacant_frames(&self) -> usize;
+    /// Push interleaved frames holding one sample per output channel
+    fn push_frames(&mut self, frames: &[f32]);
+    /// Hand over the receiving end of the MIDI channel, only the first call
+    /// returns it
+    fn take_midi_channel(&mut self) -> Option<ChannelReceiver>;
     fn get_midi_inputs(&self) -> Vec<String>;
     fn get_midi_outputs(&self) -> Vec<String>;
     fn connect_midi_input(&mut self, id: &str);
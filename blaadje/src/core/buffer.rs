@@ -0,0 +1,94 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Interleaved audio samples, shared instead of copied when passed around
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buffer {
+    pub samples: Arc<[f32]>,
+    pub sample_rate: usize,
+    pub channels: usize,
+}
+
+impl Buffer {
+    pub fn new(samples: Arc<[f32]>, sample_rate: usize, channels: usize) -> Self {
+        Self {
+            samples,
+            sample_rate,
+            channels: channels.max(1),
+        }
+    }
+
+    /// Number of frames, a frame holds one sample for every channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    /// Copy of the frames from `start` up to `end`, clamped to the buffer
+    pub fn slice(&self, start: usize, end: usize) -> Self {
+        let end = end.min(self.frames());
+        let start = start.min(end);
+        let samples = &self.samples[start * self.channels..end * self.channels];
+
+        Self::new(samples.into(), self.sample_rate, self.channels)
+    }
+
+    /// A single channel as a mono buffer
+    pub fn channel(&self, channel: usize) -> Self {
+        let samples: Vec<f32> = (0..self.frames()).map(|i| self.get(i, channel)).collect();
+
+        Self::new(samples.into(), self.sample_rate, 1)
+    }
+
+    /// The average of all channels as a mono buffer
+    pub fn mixdown(&self) -> Self {
+        let samples: Vec<f32> = self
+            .samples
+            .chunks_exact(self.channels)
+            .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+            .collect();
+
+        Self::new(samples.into(), self.sample_rate, 1)
+    }
+
+    /// Sample of a channel in a frame, silence outside of the buffer
+    pub fn get(&self, frame: usize, channel: usize) -> f32 {
+        let channel = channel.min(self.channels - 1);
+
+        self.samples
+            .get(frame * self.channels + channel)
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+impl fmt::Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<buffer {} frames, {} channels, {}Hz>",
+            self.frames(),
+            self.channels,
+            self.sample_rate
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stereo() -> Buffer {
+        Buffer::new(vec![0.0, 1.0, 0.5, -0.5, 1.0, 0.0].into(), 4, 2)
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(&stereo().channel(1).samples[..], &[1.0, -0.5, 0.0]);
+        assert_eq!(&stereo().mixdown().samples[..], &[0.5, 0.0, 0.5]);
+    }
+}
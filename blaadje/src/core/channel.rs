@@ -1,35 +1,236 @@
 use crate::{Blad, Error};
+use ringbuf::{
+    traits::{Consumer, Observer, Producer, Split},
+    HeapCons, HeapProd, HeapRb,
+};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, Thread};
+use std::time::Duration;
 
+/// Messages that fit in a channel before senders have to wait
+const CAPACITY: usize = 1024;
+
+/// How often a waiting sender checks whether the receiver is still there
+const POLL: Duration = Duration::from_millis(1);
+
+pub enum Message {
+    /// Control message nobody waits on
+    Cast(Blad),
+    /// Control message whose sender waits for the reply
+    Call(Blad, Thread),
+    /// Raw MIDI message, packed with the first byte lowest
+    Midi(u32),
+}
+
+impl Message {
+    /// The control message, MIDI messages have none
+    pub fn body(&self) -> Option<&Blad> {
+        match self {
+            Message::Cast(body) | Message::Call(body, _) => Some(body),
+            Message::Midi(_) => None,
+        }
+    }
+}
+
+/// A handled control message on its way back to the sending side, which
+/// drops it there together with its result.
+struct Returned {
+    _body: Blad,
+    result: Result<Blad, Error>,
+    answer: bool,
+}
+
+/// Sending side of the ring, shared by every clone of the channel
+struct Control {
+    requests: HeapProd<Message>,
+    returns: HeapCons<Returned>,
+}
+
+impl Control {
+    /// Drop everything the receiver handed back, keeping the first answer
+    fn drain(&mut self) -> Option<Result<Blad, Error>> {
+        let mut answer = None;
+
+        while let Some(returned) = self.returns.try_pop() {
+            if returned.answer && answer.is_none() {
+                answer = Some(returned.result);
+            }
+        }
+
+        answer
+    }
+}
+
+struct Shared {
+    control: Mutex<Control>,
+    idle: Mutex<()>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn notify(&self) {
+        // Taking the lock orders the push before a receiver starting to wait
+        drop(self.idle.lock().unwrap());
+        self.wake.notify_one();
+    }
+}
+
+/// Sending side of a channel, cheap to clone and share between evaluators.
+///
+/// Messages travel over a preallocated SPSC ring, senders take turns on the
+/// producing end. The receiver never allocates to take a message or to answer
+/// one: handled messages and their results go back over a second ring and are
+/// dropped on the sending side. Building the reply itself still happens on
+/// the receiving thread, so control messages that allocate (inserting modules,
+/// queries) belong between blocks, not in the middle of one.
+#[derive(Clone)]
 pub struct Channel {
-    messages: Vec<Blad>,
-    reply: Option<Result<Blad, Error>>,
+    shared: Arc<Shared>,
 }
 
 impl Channel {
-    pub fn new() -> Self {
-        Self {
-            messages: vec![],
-            reply: None,
+    pub fn new() -> (Self, ChannelReceiver) {
+        let (requests, requests_receiver) = HeapRb::new(CAPACITY).split();
+        // Senders drain the returns before every push, so they never hold much
+        // more than a ring's worth and the receiver does not have to drop one
+        let (returns_sender, returns) = HeapRb::new(2 * CAPACITY).split();
+
+        let shared = Arc::new(Shared {
+            control: Mutex::new(Control { requests, returns }),
+            idle: Mutex::new(()),
+            wake: Condvar::new(),
+        });
+
+        (
+            Self {
+                shared: shared.clone(),
+            },
+            ChannelReceiver {
+                requests: requests_receiver,
+                returns: returns_sender,
+                shared,
+            },
+        )
+    }
+
+    /// Send a message without expecting a reply
+    pub fn send(&self, message: Blad) {
+        let mut control = self.shared.control.lock().unwrap();
+        let mut message = Message::Cast(message);
+
+        loop {
+            control.drain();
+
+            match control.requests.try_push(message) {
+                Ok(()) => break,
+                // Without a receiver there is nobody left to act on the message
+                Err(_) if !control.requests.read_is_held() => return,
+                Err(rejected) => {
+                    message = rejected;
+                    thread::sleep(POLL);
+                }
+            }
         }
+
+        drop(control);
+        self.shared.notify();
     }
 
-    pub fn send(&mut self, message: Blad) {
-        self.messages.push(message);
+    /// Send a MIDI message, dropped when the ring is full so the MIDI
+    /// callback never waits
+    pub fn send_midi(&self, message: u32) {
+        let mut control = self.shared.control.lock().unwrap();
+        control.drain();
+
+        if control.requests.try_push(Message::Midi(message)).is_ok() {
+            drop(control);
+            self.shared.notify();
+        }
     }
 
-    pub fn messages(&self) -> &[Blad] {
-        &self.messages
+    /// Send a message and block until the receiver replies to it
+    pub fn call(&self, message: Blad) -> Result<Blad, Error> {
+        // Holding the sending side until the answer arrives keeps calls from
+        // different threads from picking up each other's replies
+        let mut control = self.shared.control.lock().unwrap();
+        let mut message = Message::Call(message, thread::current());
+
+        loop {
+            control.drain();
+
+            match control.requests.try_push(message) {
+                Ok(()) => break,
+                Err(_) if !control.requests.read_is_held() => return Err(Error::ChannelClosed),
+                Err(rejected) => {
+                    message = rejected;
+                    thread::sleep(POLL);
+                }
+            }
+        }
+
+        self.shared.notify();
+
+        loop {
+            if let Some(result) = control.drain() {
+                return result;
+            }
+
+            if !control.requests.read_is_held() {
+                // The receiver may have answered right before going away
+                return control.drain().unwrap_or(Err(Error::ChannelClosed));
+            }
+
+            thread::park_timeout(POLL);
+        }
     }
+}
 
-    pub fn clear(&mut self) {
-        self.messages.clear();
+pub struct ChannelReceiver {
+    requests: HeapCons<Message>,
+    returns: HeapProd<Returned>,
+    shared: Arc<Shared>,
+}
+
+impl ChannelReceiver {
+    /// Take the next pending message without blocking
+    pub fn try_recv(&mut self) -> Option<Message> {
+        self.requests.try_pop()
     }
 
-    pub fn reply(&mut self, result: Result<Blad, Error>) {
-        self.reply = Some(result);
+    /// Wait up to `timeout` for the next message
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<Message> {
+        let idle = self.shared.idle.lock().unwrap();
+
+        if self.requests.is_empty() {
+            let _ = self.shared.wake.wait_timeout(idle, timeout).unwrap();
+        } else {
+            drop(idle);
+        }
+
+        self.try_recv()
     }
 
-    pub fn take_reply(&mut self) -> Option<Result<Blad, Error>> {
-        self.reply.take()
+    /// Answer a message and hand it back to the sending side to be dropped,
+    /// casts and MIDI messages are not answered
+    pub fn reply(&mut self, message: Message, result: Result<Blad, Error>) {
+        let (body, caller) = match message {
+            Message::Cast(body) => (body, None),
+            Message::Call(body, caller) => (body, Some(caller)),
+            Message::Midi(_) => return,
+        };
+
+        let returned = Returned {
+            _body: body,
+            result,
+            answer: caller.is_some(),
+        };
+
+        // Only full once the sending side has gone away, dropping it here is
+        // all that is left to do then
+        let _ = self.returns.try_push(returned);
+
+        if let Some(caller) = caller {
+            caller.unpark();
+        }
     }
 }
@@ -1,4 +1,4 @@
-use super::{Blad, Channel, Error};
+use super::{Blad, Channel, ChannelReceiver, Error};
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Mutex};
@@ -14,21 +14,21 @@ pub struct Environment {
     mode: Mode,
     values: HashMap<String, Blad>,
     parent: Option<Arc<Mutex<Environment>>>,
-    pub channel: Arc<Mutex<Channel>>,
+    pub channel: Channel,
 }
 
 impl Environment {
-    pub fn new() -> (Self, Arc<Mutex<Channel>>) {
-        let channel = Arc::new(Mutex::new(Channel::new()));
+    pub fn new() -> (Self, ChannelReceiver) {
+        let (channel, receiver) = Channel::new();
 
         (
             Self {
                 mode: Mode::Normal,
                 values: HashMap::new(),
                 parent: None,
-                channel: channel.clone(),
+                channel,
             },
-            channel,
+            receiver,
         )
     }
 
@@ -46,6 +46,20 @@ impl Environment {
         }
     }
 
+    /// Insert an empty environment between this one and its parent. Values
+    /// defined there can be shadowed here, even outside of live mode.
+    pub fn insert_parent(&mut self) -> Arc<Mutex<Environment>> {
+        let parent = Arc::new(Mutex::new(Self {
+            mode: Mode::Normal,
+            values: HashMap::new(),
+            parent: self.parent.take(),
+            channel: self.channel.clone(),
+        }));
+
+        self.parent = Some(parent.clone());
+        parent
+    }
+
     pub fn set_parent(&mut self, env: Arc<Mutex<Environment>>) {
         self.parent = Some(env);
     }
@@ -79,20 +93,12 @@ impl Environment {
         self.values.iter().collect()
     }
 
-    pub fn channel_cast(&mut self, message: Blad) {
-        let mut channel = self.channel.lock().unwrap();
-        channel.send(message);
+    pub fn channel_cast(&self, message: Blad) {
+        self.channel.send(message);
     }
 
-    pub fn channel_call(&mut self, message: Blad) -> Result<Blad, Error> {
-        self.channel_cast(message);
-
-        loop {
-            let mut channel = self.channel.lock().unwrap();
-            if let Some(response) = channel.take_reply() {
-                return response;
-            }
-        }
+    pub fn channel_call(&self, message: Blad) -> Result<Blad, Error> {
+        self.channel.call(message)
     }
 }
 
@@ -106,6 +112,7 @@ impl fmt::Debug for Environment {
 mod tests {
     use super::super::Literal;
     use super::*;
+    use std::thread;
 
     #[test]
     fn child_scope_should_inherit_root() {
@@ -115,18 +122,18 @@ mod tests {
 
         {
             let mut env = root.lock().unwrap();
-            let _ = env.set("x", Blad::Literal(Literal::Usize(5)));
+            let _ = env.set("x", Blad::Literal(Literal::I64(5)));
         }
 
         let env = child.lock().unwrap();
-        assert_eq!(env.get("x").unwrap(), Blad::Literal(Literal::Usize(5)));
+        assert_eq!(env.get("x").unwrap(), Blad::Literal(Literal::I64(5)));
     }
 
     #[test]
     fn immutability() {
         let (mut env, _) = Environment::new();
 
-        matches!(env.set("x", Blad::Literal(Literal::Usize(10))), Ok(_));
+        matches!(env.set("x", Blad::Literal(Literal::I64(10))), Ok(_));
         matches!(
             env.set("x", Blad::Unit),
             Err(Error::AttemptToRedefineVariable(_))
@@ -137,12 +144,65 @@ mod tests {
     fn live_mode() {
         let (mut env, _) = Environment::new();
 
-        env.set("x", Blad::Literal(Literal::Usize(10))).unwrap();
+        env.set("x", Blad::Literal(Literal::I64(10))).unwrap();
 
         env.live_mode();
 
-        env.set("x", Blad::Literal(Literal::Usize(2))).unwrap();
+        env.set("x", Blad::Literal(Literal::I64(2))).unwrap();
+
+        assert_eq!(env.get("x").unwrap(), Blad::Literal(Literal::I64(2)));
+    }
+
+    #[test]
+    fn concurrent_calls() {
+        let (env, mut channel) = Environment::new();
+        let env = Arc::new(env);
+
+        // Echo every message back to its caller
+        let responder = thread::spawn(move || {
+            let mut replied = 0;
+
+            while replied < 200 {
+                if let Some(message) = channel.try_recv() {
+                    let body = message.body().unwrap().clone();
+                    channel.reply(message, Ok(body));
+                    replied += 1;
+                }
+            }
+        });
+
+        let callers: Vec<_> = (0..2)
+            .map(|caller| {
+                let env = env.clone();
+
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let message = Blad::Literal(Literal::I64(caller * 1000 + i));
+                        assert_eq!(env.channel_call(message.clone()).unwrap(), message);
+                    }
+                })
+            })
+            .collect();
+
+        for caller in callers {
+            caller.join().unwrap();
+        }
+
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn closed_channel() {
+        let (env, mut channel) = Environment::new();
+
+        // Drop the message without replying
+        let responder = thread::spawn(move || loop {
+            if channel.try_recv().is_some() {
+                break;
+            }
+        });
 
-        assert_eq!(env.get("x").unwrap(), Blad::Literal(Literal::Usize(2)));
+        assert_eq!(env.channel_call(Blad::Unit), Err(Error::ChannelClosed));
+        responder.join().unwrap();
     }
 }
@@ -1,12 +1,18 @@
-use super::Blad;
+use super::{Blad, Span};
 use std::error::Error as StdError;
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
+    At(Span, Box<Error>),
     AttemptToRedefineVariable(String),
+    Call(String, Box<Error>),
+    ChannelClosed,
+    DivisionByZero,
     ExpectedAtom(Blad),
+    ExpectedBuffer(Blad),
     ExpectedF32(Blad),
+    ExpectedI64(Blad),
     ExpectedList(Blad),
     ExpectedNumber(Blad),
     ExpectedProcedure(Blad),
@@ -24,24 +30,137 @@ pub enum Error {
     IncorrectPropertyPair(String, Blad),
     IncorrectVariableDeclaration(Blad, Blad),
     IncorrectVariableDestructuring(usize, usize),
+    IntegerOverflow,
+    InvalidChannel(Blad),
+    InvalidEscape(char),
     InvalidNote(String),
     InvalidProperty(String),
+    InvalidRangeStep(i64),
     InvalidToken(String),
     ModuleIdNotFound(String),
     ModuleNotFound(usize),
-    ParseError(usize),
+    ModuleOptionsChanged(String),
+    OutOfModules,
+    OutOfPatchPoints,
+    SystemError(String),
     UnableToConvertToString(Blad),
+    UnclosedList,
     UndefinedOperator(String),
     UndefinedSymbol(String),
     UnexpectedToken(String),
     UnknownModule(String),
     UnsupportedNumericType(String),
+    UnterminatedComment,
+    UnterminatedString,
     WavError,
 }
 
+impl Error {
+    /// Attach a source position, unless a more precise one is already known
+    pub fn at(self, span: &Span) -> Self {
+        match self {
+            Error::At(_, _) => self,
+            _ => Error::At(span.clone(), Box::new(self)),
+        }
+    }
+
+    /// Record the function call this error passed through
+    pub fn in_call(self, name: &str) -> Self {
+        Error::Call(name.to_string(), Box::new(self))
+    }
+
+    /// The underlying error without its source position and call stack
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::At(_, error) | Error::Call(_, error) => error.root_cause(),
+            _ => self,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Error::At(span, error) => write!(f, "{}\n{}", error, span),
+            Error::AttemptToRedefineVariable(s) => write!(f, "`{}` is already defined", s),
+            Error::Call(name, error) => write!(f, "{}\nin call to `{}`", error, name),
+            Error::ChannelClosed => write!(f, "the channel is closed"),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::ExpectedAtom(b) => write!(f, "expected an atom, got `{}`", b),
+            Error::ExpectedBuffer(b) => write!(f, "expected a buffer, got `{}`", b),
+            Error::ExpectedF32(b) => write!(f, "expected a float, got `{}`", b),
+            Error::ExpectedI64(b) => write!(f, "expected an integer, got `{}`", b),
+            Error::ExpectedList(b) => write!(f, "expected a list, got `{}`", b),
+            Error::ExpectedNumber(b) => write!(f, "expected a number, got `{}`", b),
+            Error::ExpectedProcedure(b) => write!(f, "expected a procedure, got `{}`", b),
+            Error::ExpectedSameTypes(a, b) => {
+                write!(
+                    f,
+                    "expected values of the same type, got `{}` and `{}`",
+                    a, b
+                )
+            }
+            Error::ExpectedScreechModule(b) => write!(f, "expected a module, got `{}`", b),
+            Error::ExpectedScreechSignal(b) => write!(f, "expected a signal, got `{}`", b),
+            Error::ExpectedString(b) => write!(f, "expected a string, got `{}`", b),
+            Error::ExpectedSymbol(b) => write!(f, "expected a symbol, got `{}`", b),
+            Error::ExpectedUsize(b) => {
+                write!(f, "expected a non-negative integer, got `{}`", b)
+            }
+            Error::FileError => write!(f, "unable to read file"),
+            Error::IncorrectArguments(errors) => {
+                write!(f, "incorrect arguments:")?;
+
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+
+                Ok(())
+            }
+            Error::IncorrectLambdaSyntax(b) => write!(f, "incorrect function syntax `{}`", b),
+            Error::IncorrectMacroSyntax(b) => write!(f, "incorrect macro syntax `{}`", b),
+            Error::IncorrectNumberOfArguments(got, expected) => {
+                write!(f, "expected {} argument(s), got {}", expected, got)
+            }
+            Error::IncorrectPropertyPair(property, b) => {
+                write!(f, "incorrect value `{}` for property `{}`", b, property)
+            }
+            Error::IncorrectVariableDeclaration(key, value) => {
+                write!(f, "unable to bind `{}` to `{}`", value, key)
+            }
+            Error::IncorrectVariableDestructuring(keys, values) => {
+                write!(
+                    f,
+                    "unable to destructure {} value(s) into {} key(s)",
+                    values, keys
+                )
+            }
+            Error::IntegerOverflow => write!(f, "integer overflow"),
+            Error::InvalidChannel(b) => write!(f, "invalid channel `{}`", b),
+            Error::InvalidEscape(c) => write!(f, "invalid escape sequence `\\{}`", c),
+            Error::InvalidNote(s) => write!(f, "invalid note `{}`", s),
+            Error::InvalidProperty(s) => write!(f, "invalid property `{}`", s),
+            Error::InvalidRangeStep(step) => write!(f, "invalid range step {}", step),
+            Error::InvalidToken(s) => write!(f, "invalid token `{}`", s),
+            Error::ModuleIdNotFound(s) => write!(f, "no module with id `{}`", s),
+            Error::ModuleNotFound(id) => write!(f, "module {} not found", id),
+            Error::ModuleOptionsChanged(s) => {
+                write!(f, "module `{}` already exists with other options", s)
+            }
+            Error::OutOfModules => write!(f, "out of modules"),
+            Error::OutOfPatchPoints => write!(f, "out of patch points"),
+            Error::SystemError(s) => write!(f, "system error: {}", s),
+            Error::UnableToConvertToString(b) => write!(f, "unable to convert `{}` to a string", b),
+            Error::UnclosedList => write!(f, "unclosed `(`"),
+            Error::UndefinedOperator(s) => write!(f, "undefined operator `{}`", s),
+            Error::UndefinedSymbol(s) => write!(f, "undefined symbol `{}`", s),
+            Error::UnexpectedToken(s) => write!(f, "unexpected `{}`", s),
+            Error::UnknownModule(s) => write!(f, "unknown module `{}`", s),
+            Error::UnsupportedNumericType(s) => write!(f, "unsupported number `{}`", s),
+            Error::UnterminatedComment => write!(f, "unterminated block comment"),
+            Error::UnterminatedString => write!(f, "unterminated string"),
+            Error::WavError => write!(f, "unable to decode WAV file"),
+        }
     }
 }
 
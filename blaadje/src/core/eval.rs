@@ -1,10 +1,16 @@
 use super::operators::{
-    process_add, process_append, process_call, process_cast, process_cons, process_equal,
-    process_greater_than, process_head, process_if, process_lambda, process_lambda_call,
-    process_less_than, process_let, process_list, process_macro, process_macro_call,
-    process_samples, process_string, process_subtract, process_tail,
+    process_abs, process_add, process_all, process_any, process_append, process_buffer_duration,
+    process_buffer_length, process_buffer_slice, process_call, process_cast, process_concat,
+    process_cons, process_divide, process_drop, process_enumerate, process_equal, process_filter,
+    process_find, process_fold, process_greater_than, process_head, process_if, process_lambda,
+    process_lambda_call, process_length, process_less_than, process_let, process_list,
+    process_macro, process_macro_call, process_map, process_max, process_min, process_modulo,
+    process_multiply, process_nth, process_range, process_reduce, process_reverse, process_samples,
+    process_sort, process_sort_by, process_string, process_subtract, process_tail, process_take,
+    process_zip,
 };
-use super::{Blad, Environment, Error, Keyword};
+use super::{Blad, Environment, Error, Keyword, Span};
+use std::borrow::Cow;
 use std::sync::{Arc, Mutex};
 
 pub fn eval_nodes(nodes: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
@@ -14,57 +20,210 @@ pub fn eval_nodes(nodes: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad,
     }
 }
 
+/// Call frames kept for tail calls, recursion beyond this isn't recorded
+const MAX_FRAMES: usize = 64;
+
+/// How `eval` continues after evaluating a single step. Expressions in tail
+/// position are evaluated by looping instead of recursing, so they don't grow
+/// the native stack.
+enum Step {
+    Done(Blad),
+    /// Continue with the expression wrapped by a span
+    Spanned,
+    /// Continue with an item of the current list, like a branch of `if`
+    Item(usize),
+    /// Continue with the body of a lambda or the expansion of a macro
+    Call(String, Blad, Arc<Mutex<Environment>>),
+}
+
+struct Frame {
+    name: String,
+    span: Option<Span>,
+}
+
 pub fn eval(program: &Blad, env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    let mut program = Cow::Borrowed(program);
+    let mut env = env;
+    let mut span = None;
+    let mut frames: Vec<Frame> = vec![];
+
+    loop {
+        let step = match eval_step(&program, &env) {
+            Ok(step) => step,
+            Err(error) => return Err(unwind(error, span, frames)),
+        };
+
+        match step {
+            Step::Done(blad) => return Ok(blad),
+            Step::Spanned => {
+                let (inner_span, inner) = into_spanned(program);
+                span = Some(inner_span);
+                program = inner;
+            }
+            Step::Item(index) => program = into_item(program, index),
+            Step::Call(name, body, inner_env) => {
+                let call_span = span.take();
+
+                // Direct recursion only keeps the outermost call
+                let recursive = frames.last().is_some_and(|f| f.name == name);
+                if !recursive && frames.len() < MAX_FRAMES {
+                    frames.push(Frame {
+                        name,
+                        span: call_span,
+                    });
+                }
+
+                program = Cow::Owned(body);
+                env = inner_env;
+            }
+        }
+    }
+}
+
+fn eval_step(program: &Blad, env: &Arc<Mutex<Environment>>) -> Result<Step, Error> {
     match program {
         Blad::Unit
         | Blad::Atom(_)
+        | Blad::Buffer(_)
         | Blad::Literal(_)
         | Blad::Keyword(_)
         | Blad::Screech(_)
-        | Blad::Lambda(_, _, _)
-        | Blad::Macro(_, _) => Ok(program.clone()),
-        Blad::Quote(blad) => Ok(*blad.clone()),
+        | Blad::Lambda(_, _, _, _)
+        | Blad::Macro(_, _, _) => Ok(Step::Done(program.clone())),
+        Blad::Quote(blad) => Ok(Step::Done(*blad.clone())),
+        Blad::Spanned(_, _) => Ok(Step::Spanned),
         Blad::Symbol(string) => {
             let env = env.lock().unwrap();
             env.get(string)
+                .map(Step::Done)
                 .ok_or(Error::UndefinedSymbol(string.clone()))
         }
-        Blad::List(ref list) if list.is_empty() => Ok(Blad::Unit),
+        Blad::List(ref list) if list.is_empty() => Ok(Step::Done(Blad::Unit)),
         Blad::List(list) => {
             let operator = eval(list.get(0).unwrap(), env.clone())?;
             let rest = &list[1..list.len()];
 
-            match &operator {
+            let result = match operator {
                 Blad::Keyword(keyword) => match keyword {
+                    Keyword::Abs => process_abs(rest, env.clone()),
                     Keyword::Add => process_add(rest, env.clone()),
+                    Keyword::All => process_all(rest, env.clone()),
+                    Keyword::Any => process_any(rest, env.clone()),
                     Keyword::Append => process_append(rest, env.clone()),
+                    Keyword::BufferDuration => process_buffer_duration(rest, env.clone()),
+                    Keyword::BufferLength => process_buffer_length(rest, env.clone()),
+                    Keyword::BufferSlice => process_buffer_slice(rest, env.clone()),
                     Keyword::Call => process_call(rest, env.clone()),
                     Keyword::Cast => process_cast(rest, env.clone()),
+                    Keyword::Concat => process_concat(rest, env.clone()),
                     Keyword::Cons => process_cons(rest, env.clone()),
+                    Keyword::Divide => process_divide(rest, env.clone()),
+                    Keyword::Drop => process_drop(rest, env.clone()),
+                    Keyword::Enumerate => process_enumerate(rest, env.clone()),
                     Keyword::Equal => process_equal(rest, env.clone()),
+                    Keyword::Filter => process_filter(rest, env.clone()),
+                    Keyword::Find => process_find(rest, env.clone()),
+                    Keyword::Fold => process_fold(rest, env.clone()),
                     Keyword::GreaterThan => process_greater_than(rest, env.clone()),
                     Keyword::Head => process_head(rest, env.clone()),
-                    Keyword::If => process_if(rest, env.clone()),
+                    Keyword::If => {
+                        // Skip the operator, the branch is in tail position
+                        return Ok(Step::Item(process_if(rest, env.clone())? + 1));
+                    }
                     Keyword::Lambda => process_lambda(rest, env.clone()),
+                    Keyword::Length => process_length(rest, env.clone()),
                     Keyword::LessThan => process_less_than(rest, env.clone()),
                     Keyword::Let => process_let(rest, env.clone()),
                     Keyword::List => process_list(rest, env.clone()),
                     Keyword::Macro => process_macro(rest, env.clone()),
+                    Keyword::Map => process_map(rest, env.clone()),
+                    Keyword::Max => process_max(rest, env.clone()),
+                    Keyword::Min => process_min(rest, env.clone()),
+                    Keyword::Modulo => process_modulo(rest, env.clone()),
+                    Keyword::Multiply => process_multiply(rest, env.clone()),
+                    Keyword::Nth => process_nth(rest, env.clone()),
+                    Keyword::Range => process_range(rest, env.clone()),
+                    Keyword::Reduce => process_reduce(rest, env.clone()),
+                    Keyword::Reverse => process_reverse(rest, env.clone()),
                     Keyword::Samples => process_samples(rest, env.clone()),
+                    Keyword::Sort => process_sort(rest, env.clone()),
+                    Keyword::SortBy => process_sort_by(rest, env.clone()),
                     Keyword::String => process_string(rest, env.clone()),
                     Keyword::Subtract => process_subtract(rest, env.clone()),
                     Keyword::Tail => process_tail(rest, env.clone()),
+                    Keyword::Take => process_take(rest, env.clone()),
+                    Keyword::Zip => process_zip(rest, env.clone()),
                 },
-                Blad::Lambda(closure, params, body) => {
-                    process_lambda_call(closure, params, body, rest, env.clone())
+                Blad::Lambda(closure, params, body, name) => {
+                    let name = name.unwrap_or_else(|| "<lambda>".to_string());
+                    let inner_env =
+                        process_lambda_call(&closure, &params, &name, rest, env.clone())?;
+
+                    return Ok(Step::Call(name, *body, inner_env));
+                }
+                Blad::Macro(params, body, name) => {
+                    let name = name.unwrap_or_else(|| "<macro>".to_string());
+                    let output = process_macro_call(&params, &body, &name, rest, env.clone())?;
+
+                    return Ok(Step::Call(name, output, env.clone()));
                 }
-                Blad::Macro(params, body) => process_macro_call(params, body, rest, env.clone()),
                 _ => Err(Error::ExpectedProcedure(operator)),
-            }
+            };
+
+            result.map(Step::Done)
         }
     }
 }
 
+/// Take the expression out of a spanned node, without cloning owned nodes
+fn into_spanned(program: Cow<Blad>) -> (Span, Cow<Blad>) {
+    match program {
+        Cow::Borrowed(Blad::Spanned(span, blad)) => (span.clone(), Cow::Borrowed(blad)),
+        Cow::Owned(Blad::Spanned(span, blad)) => (span, Cow::Owned(*blad)),
+        _ => unreachable!("expected a spanned node"),
+    }
+}
+
+/// Take an item out of a list node, without cloning owned nodes
+fn into_item(program: Cow<Blad>, index: usize) -> Cow<Blad> {
+    match program {
+        Cow::Borrowed(Blad::List(list)) => Cow::Borrowed(&list[index]),
+        Cow::Owned(Blad::List(mut list)) => Cow::Owned(list.swap_remove(index)),
+        _ => unreachable!("expected a list node"),
+    }
+}
+
+/// Add the position and the tail calls an error passed through, innermost first
+fn unwind(error: Error, span: Option<Span>, frames: Vec<Frame>) -> Error {
+    let mut error = match span {
+        Some(span) => error.at(&span),
+        None => error,
+    };
+
+    for frame in frames.into_iter().rev() {
+        error = error.in_call(&frame.name);
+
+        if let Some(span) = frame.span {
+            error = error.at(&span);
+        }
+    }
+
+    error
+}
+
+/// Call a function with arguments that are already evaluated
+pub fn apply(
+    function: &Blad,
+    arguments: Vec<Blad>,
+    env: Arc<Mutex<Environment>>,
+) -> Result<Blad, Error> {
+    let mut call = vec![function.clone()];
+    // Quote the arguments so they aren't evaluated a second time
+    call.extend(arguments.into_iter().map(|a| Blad::Quote(Box::new(a))));
+
+    eval(&Blad::List(call), env)
+}
+
 pub fn args(list: &[Blad], args: usize) -> Result<&[Blad], Error> {
     if list.len() != args {
         Err(Error::IncorrectNumberOfArguments(list.len(), args))
@@ -84,7 +243,7 @@ pub fn args_min(list: &[Blad], args: usize) -> Result<&[Blad], Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::run;
+    use crate::{run, Literal};
 
     #[test]
     fn quote_keywords() {
@@ -98,4 +257,36 @@ mod tests {
             Blad::List(vec![Blad::Keyword(Keyword::Let)])
         );
     }
+
+    #[test]
+    fn error_position() {
+        let error = run("(+ 1\n   (* 2 :a))").unwrap_err();
+
+        assert_eq!(
+            error.root_cause(),
+            &Error::ExpectedNumber(Blad::Atom(":a".into()))
+        );
+        assert_eq!(
+            error.to_string(),
+            "expected a number, got `:a`\n --> line 2, column 4\n  |\n2 |    (* 2 :a))\n  |    ^"
+        );
+    }
+    #[test]
+    fn tail_calls_million() {
+        // An interpreted fold, every step is a tail call from an `if` in a
+        // lambda body so the native stack must not grow
+        let code = "
+            (let fold-to (fn (i n acc f)
+                (if (< i n)
+                    (fold-to (+ i 1) n (f acc i) f)
+                    acc)))
+
+            (fold-to 0 1000000 0 (fn (acc x) (+ acc x)))
+        ";
+
+        assert_eq!(
+            run(code).unwrap(),
+            Blad::Literal(Literal::I64(499999500000))
+        );
+    }
 }
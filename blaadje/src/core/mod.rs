@@ -1,3 +1,4 @@
+mod buffer;
 mod channel;
 mod env;
 mod error;
@@ -5,32 +6,49 @@ mod eval;
 pub mod notes;
 mod operators;
 mod parse;
+mod span;
+mod wav;
 
-pub use channel::Channel;
+pub use buffer::Buffer;
+pub use channel::{Channel, ChannelReceiver, Message};
 pub use env::Environment;
 pub use error::Error;
-pub use eval::{args, args_min, eval, eval_nodes};
+pub use eval::{apply, args, args_min, eval, eval_nodes};
 use notes::atom_to_pitch;
 pub use parse::parse;
 use screech::Signal;
+pub use span::Span;
 use std::convert::Into;
 use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum Blad {
     Atom(String),
+    Buffer(Buffer),
     Keyword(Keyword),
-    Lambda(Environment, Box<Blad>, Box<Blad>),
+    Lambda(Environment, Box<Blad>, Box<Blad>, Option<String>),
     List(Vec<Blad>),
     Literal(Literal),
-    Macro(Box<Blad>, Box<Blad>),
+    Macro(Box<Blad>, Box<Blad>, Option<String>),
     Quote(Box<Blad>),
     Screech(Screech),
+    Spanned(Span, Box<Blad>),
     Symbol(String),
     Unit,
 }
 
 impl Blad {
+    /// Remove the source positions of this node and all of its children, for
+    /// code that is handled as data instead of being evaluated.
+    pub fn strip_spans(&self) -> Blad {
+        match self {
+            Blad::Spanned(_, blad) => blad.strip_spans(),
+            Blad::List(list) => Blad::List(list.iter().map(Blad::strip_spans).collect()),
+            Blad::Quote(blad) => Blad::Quote(Box::new(blad.strip_spans())),
+            _ => self.clone(),
+        }
+    }
+
     pub fn get_atom(&self) -> Result<&str, Error> {
         match self {
             Blad::Atom(s) => Ok(s),
@@ -38,6 +56,13 @@ impl Blad {
         }
     }
 
+    pub fn get_buffer(&self) -> Result<&Buffer, Error> {
+        match self {
+            Blad::Buffer(buffer) => Ok(buffer),
+            _ => Err(Error::ExpectedBuffer(self.clone())),
+        }
+    }
+
     pub fn get_list(&self) -> Result<&[Blad], Error> {
         match self {
             Blad::List(list) => Ok(list),
@@ -68,11 +93,18 @@ impl Blad {
 
     pub fn get_usize(&self) -> Result<usize, Error> {
         match self {
-            Blad::Literal(Literal::Usize(int)) => Ok(*int),
+            Blad::Literal(Literal::I64(int)) if *int >= 0 => Ok(*int as usize),
             _ => Err(Error::ExpectedUsize(self.clone())),
         }
     }
 
+    pub fn get_i64(&self) -> Result<i64, Error> {
+        match self {
+            Blad::Literal(Literal::I64(int)) => Ok(*int),
+            _ => Err(Error::ExpectedI64(self.clone())),
+        }
+    }
+
     pub fn get_string(&self) -> Result<&str, Error> {
         match self {
             Blad::Literal(Literal::String(s)) => Ok(s),
@@ -101,6 +133,7 @@ impl fmt::Display for Blad {
 
         match self {
             Blad::Atom(a) => output.push_str(a),
+            Blad::Buffer(a) => output.push_str(&a.to_string()),
             Blad::Keyword(a) => output.push_str(&a.to_string()),
             Blad::List(items) => {
                 output.push('(');
@@ -120,16 +153,17 @@ impl fmt::Display for Blad {
                 output.push_str(&a.to_string())
             }
             Blad::Screech(a) => output.push_str(&a.to_string()),
+            Blad::Spanned(_, a) => output.push_str(&a.to_string()),
             Blad::Symbol(a) => output.push_str(a),
             Blad::Unit => output.push_str("()"),
-            Blad::Lambda(_, keys, body) => {
+            Blad::Lambda(_, keys, body, _) => {
                 output.push_str("(fn ");
                 output.push_str(&keys.to_string());
                 output.push(' ');
                 output.push_str(&body.to_string());
                 output.push(')');
             }
-            Blad::Macro(keys, body) => {
+            Blad::Macro(keys, body, _) => {
                 output.push_str("(macro ");
                 output.push_str(&keys.to_string());
                 output.push(' ');
@@ -145,7 +179,10 @@ impl fmt::Display for Blad {
 impl PartialEq for Blad {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (Blad::Spanned(_, a), b) => a.as_ref() == b,
+            (a, Blad::Spanned(_, b)) => a == b.as_ref(),
             (Blad::Atom(a), Blad::Atom(b)) => a == b,
+            (Blad::Buffer(a), Blad::Buffer(b)) => a == b,
             (Blad::Keyword(a), Blad::Keyword(b)) => a == b,
             (Blad::List(a), Blad::List(b)) => a == b,
             (Blad::Literal(a), Blad::Literal(b)) => a == b,
@@ -160,7 +197,7 @@ impl PartialEq for Blad {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
-    Usize(usize),
+    I64(i64),
     F32(f32),
     String(String),
 }
@@ -168,11 +205,21 @@ pub enum Literal {
 impl Literal {
     pub fn to_string(&self) -> String {
         match self {
-            Literal::Usize(n) => n.to_string(),
+            Literal::I64(n) => n.to_string(),
             Literal::F32(n) => n.to_string(),
             Literal::String(s) => {
                 let mut output = "\"".to_string();
-                output.push_str(s);
+
+                for c in s.chars() {
+                    match c {
+                        '"' => output.push_str("\\\""),
+                        '\\' => output.push_str("\\\\"),
+                        '\n' => output.push_str("\\n"),
+                        '\t' => output.push_str("\\t"),
+                        c => output.push(c),
+                    }
+                }
+
                 output.push('"');
                 output
             }
@@ -182,20 +229,47 @@ impl Literal {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Keyword {
+    Abs,
     Add,
+    All,
+    Any,
     Append,
+    BufferDuration,
+    BufferLength,
+    BufferSlice,
+    Concat,
     Cons,
+    Divide,
+    Drop,
+    Enumerate,
     Equal,
+    Filter,
+    Find,
+    Fold,
     GreaterThan,
     Head,
     If,
     Lambda,
+    Length,
     LessThan,
     Let,
     List,
     Macro,
+    Map,
+    Max,
+    Min,
+    Modulo,
+    Multiply,
+    Nth,
+    Range,
+    Reduce,
+    Reverse,
+    Sort,
+    SortBy,
     Subtract,
     Tail,
+    Take,
+    Zip,
     Cast,
     Call,
     Samples,
@@ -207,22 +281,49 @@ impl fmt::Display for Keyword {
         let string = match self {
             Keyword::Add => "+",
             Keyword::Subtract => "-",
+            Keyword::Multiply => "*",
+            Keyword::Divide => "/",
             Keyword::LessThan => "<",
             Keyword::Equal => "=",
             Keyword::GreaterThan => ">",
+            Keyword::Abs => "abs",
+            Keyword::All => "all?",
+            Keyword::Any => "any?",
             Keyword::Append => "append",
+            Keyword::BufferDuration => "buffer-duration",
+            Keyword::BufferLength => "buffer-length",
+            Keyword::BufferSlice => "buffer-slice",
             Keyword::Call => "call",
             Keyword::Cast => "cast",
+            Keyword::Concat => "concat",
             Keyword::Cons => "cons",
+            Keyword::Drop => "drop",
+            Keyword::Enumerate => "enumerate",
+            Keyword::Filter => "filter",
+            Keyword::Find => "find",
             Keyword::Lambda => "fn",
+            Keyword::Fold => "fold",
             Keyword::Head => "head",
             Keyword::If => "if",
+            Keyword::Length => "length",
             Keyword::Let => "let",
             Keyword::List => "list",
             Keyword::Macro => "macro",
+            Keyword::Map => "map",
+            Keyword::Max => "max",
+            Keyword::Min => "min",
+            Keyword::Modulo => "mod",
+            Keyword::Nth => "nth",
+            Keyword::Range => "range",
+            Keyword::Reduce => "reduce",
+            Keyword::Reverse => "reverse",
             Keyword::Samples => "samples",
+            Keyword::Sort => "sort",
+            Keyword::SortBy => "sort-by",
             Keyword::String => "string",
             Keyword::Tail => "tail",
+            Keyword::Take => "take",
+            Keyword::Zip => "zip",
         };
 
         write!(f, "{}", string)
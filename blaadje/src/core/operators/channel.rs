@@ -6,16 +6,18 @@ pub fn process_call(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad,
     args(list, 1)?;
 
     let message = eval(&list[0], env.clone())?;
-    let mut env = env.lock().unwrap();
 
-    env.channel_call(message)
+    // Don't hold on to the environment while waiting for the reply
+    let channel = env.lock().unwrap().channel.clone();
+
+    channel.call(message)
 }
 
 pub fn process_cast(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
     args(list, 1)?;
 
     let message = eval(&list[0], env.clone())?;
-    let mut env = env.lock().unwrap();
+    let env = env.lock().unwrap();
 
     env.channel_cast(message);
 
@@ -2,20 +2,25 @@ use super::super::{args, eval};
 use crate::{Blad, Environment, Error, Literal};
 use std::sync::{Arc, Mutex};
 
-pub fn process_if(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+/// Evaluate the condition and return the index of the branch to take, the
+/// branch is in tail position so `eval` evaluates it.
+pub fn process_if(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<usize, Error> {
     args(list, 3)?;
 
     let condition = eval(&list[0], env.clone())?;
-    let right = &list[1];
-    let left = &list[2];
 
-    match condition {
-        Blad::Unit => eval(left, env.clone()),
-        Blad::Literal(Literal::Usize(0)) => eval(left, env.clone()),
-        _ => eval(right, env.clone()),
+    if truthy(&condition) {
+        Ok(1)
+    } else {
+        Ok(2)
     }
 }
 
+/// Everything except `()` and `0` counts as true
+pub fn truthy(blad: &Blad) -> bool {
+    !matches!(blad, Blad::Unit | Blad::Literal(Literal::I64(0)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -25,7 +30,7 @@ mod tests {
     fn truthy() {
         assert_eq!(
             run("(if (= 42 42) 12 6)").unwrap(),
-            Blad::Literal(Literal::Usize(12)),
+            Blad::Literal(Literal::I64(12)),
         );
     }
 
@@ -33,7 +38,7 @@ mod tests {
     fn falsy() {
         assert_eq!(
             run("(if (= 42 7) 12 6)").unwrap(),
-            Blad::Literal(Literal::Usize(6)),
+            Blad::Literal(Literal::I64(6)),
         );
     }
 }
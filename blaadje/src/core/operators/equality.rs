@@ -1,5 +1,6 @@
 use super::super::{args, eval};
 use crate::{Blad, Environment, Error, Literal};
+use std::cmp::Ordering;
 use std::sync::{Arc, Mutex};
 
 pub fn process_equal(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
@@ -8,42 +9,53 @@ pub fn process_equal(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad
     let a = eval(&list[0], env.clone())?;
     let b = eval(&list[1], env.clone())?;
 
-    if a == b {
-        Ok(Blad::Literal(Literal::Usize(1)))
-    } else {
-        Ok(Blad::Literal(Literal::Usize(0)))
+    // Comparing numbers of different types is most likely a mistake
+    if let (Blad::Literal(Literal::I64(_)), Blad::Literal(Literal::F32(_)))
+    | (Blad::Literal(Literal::F32(_)), Blad::Literal(Literal::I64(_))) = (&a, &b)
+    {
+        return Err(Error::ExpectedSameTypes(a, b));
     }
+
+    Ok(boolean(a == b))
 }
 
 pub fn process_greater_than(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
     args(list, 2)?;
 
-    let a = eval(&list[0], env.clone())?;
-    let b = eval(&list[1], env.clone())?;
-
-    match (&a, &b) {
-        (Blad::Literal(Literal::Usize(x)), Blad::Literal(Literal::Usize(y))) => {
-            let output = if x > y { 1 } else { 0 };
+    let ordering = compare(&list[0], &list[1], env)?;
 
-            Ok(Blad::Literal(Literal::Usize(output)))
-        }
-        _ => Err(Error::ExpectedSameTypes(a, b)),
-    }
+    Ok(boolean(ordering == Some(Ordering::Greater)))
 }
 
 pub fn process_less_than(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
     args(list, 2)?;
 
-    let a = eval(&list[0], env.clone())?;
-    let b = eval(&list[1], env.clone())?;
+    let ordering = compare(&list[0], &list[1], env)?;
+
+    Ok(boolean(ordering == Some(Ordering::Less)))
+}
+
+fn compare(a: &Blad, b: &Blad, env: Arc<Mutex<Environment>>) -> Result<Option<Ordering>, Error> {
+    let a = eval(a, env.clone())?;
+    let b = eval(b, env.clone())?;
+
+    compare_values(&a, &b)
+}
 
-    match (&a, &b) {
-        (Blad::Literal(Literal::Usize(x)), Blad::Literal(Literal::Usize(y))) => {
-            let output = if x < y { 1 } else { 0 };
+/// Order two evaluated numbers of the same type
+pub fn compare_values(a: &Blad, b: &Blad) -> Result<Option<Ordering>, Error> {
+    match (a, b) {
+        (Blad::Literal(Literal::I64(x)), Blad::Literal(Literal::I64(y))) => Ok(x.partial_cmp(y)),
+        (Blad::Literal(Literal::F32(x)), Blad::Literal(Literal::F32(y))) => Ok(x.partial_cmp(y)),
+        _ => Err(Error::ExpectedSameTypes(a.clone(), b.clone())),
+    }
+}
 
-            Ok(Blad::Literal(Literal::Usize(output)))
-        }
-        _ => Err(Error::ExpectedSameTypes(a, b)),
+pub fn boolean(value: bool) -> Blad {
+    if value {
+        Blad::Literal(Literal::I64(1))
+    } else {
+        Blad::Literal(Literal::I64(0))
     }
 }
 
@@ -56,43 +68,60 @@ mod tests {
     fn boolean() {
         assert_eq!(
             run("(= true false)").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
         );
 
         assert_eq!(
             run("(= true true)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
 
         assert_eq!(
             run("(= false false)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
     }
 
     #[test]
     fn numeric() {
-        assert_eq!(run("(= 42 42)").unwrap(), Blad::Literal(Literal::Usize(1)));
-        assert_eq!(run("(= 42 7)").unwrap(), Blad::Literal(Literal::Usize(0)));
+        assert_eq!(run("(= 42 42)").unwrap(), Blad::Literal(Literal::I64(1)));
+        assert_eq!(run("(= 42 7)").unwrap(), Blad::Literal(Literal::I64(0)));
     }
 
     #[test]
     fn greater_than() {
-        assert_eq!(run("(> 42 42)").unwrap(), Blad::Literal(Literal::Usize(0)));
-        assert_eq!(run("(> 42 7)").unwrap(), Blad::Literal(Literal::Usize(1)));
+        assert_eq!(run("(> 42 42)").unwrap(), Blad::Literal(Literal::I64(0)));
+        assert_eq!(run("(> 42 7)").unwrap(), Blad::Literal(Literal::I64(1)));
     }
 
     #[test]
     fn less_than() {
-        assert_eq!(run("(< 7 7)").unwrap(), Blad::Literal(Literal::Usize(0)));
-        assert_eq!(run("(< 7 42)").unwrap(), Blad::Literal(Literal::Usize(1)));
+        assert_eq!(run("(< 7 7)").unwrap(), Blad::Literal(Literal::I64(0)));
+        assert_eq!(run("(< 7 42)").unwrap(), Blad::Literal(Literal::I64(1)));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(run("(= 0.5 0.5)").unwrap(), Blad::Literal(Literal::I64(1)));
+        assert_eq!(run("(> 0.8 0.2)").unwrap(), Blad::Literal(Literal::I64(1)));
+        assert_eq!(run("(< 0.8 0.2)").unwrap(), Blad::Literal(Literal::I64(0)));
+    }
+
+    #[test]
+    fn mixed_types() {
+        assert!(matches!(
+            run("(= 1 1.0)").unwrap_err().root_cause(),
+            Error::ExpectedSameTypes(_, _)
+        ));
+
+        assert!(matches!(
+            run("(< 1 2.0)").unwrap_err().root_cause(),
+            Error::ExpectedSameTypes(_, _)
+        ));
     }
 
     #[test]
     fn unit() {
-        assert_eq!(
-            run("(= '() '())").unwrap(),
-            Blad::Literal(Literal::Usize(1))
-        );
+        assert_eq!(run("(= '() '())").unwrap(), Blad::Literal(Literal::I64(1)));
     }
 }
@@ -1,55 +1,192 @@
-use super::super::{args, eval};
+use super::super::wav::read_wav;
+use super::super::{args, args_min, eval};
 use crate::{Blad, Environment, Error, Literal};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use wavv::{Data, Wav};
 
+/// `(samples path)` loads all channels of a WAV file, `(samples path channel)`
+/// only `:left`, `:right`, a channel number or `:mix` for all channels mixed
+/// down to mono. The buffer keeps the rate of the file, samples convert it
+/// while playing.
 pub fn process_samples(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
-    args(list, 1)?;
+    args_min(list, 1)?;
+
+    if list.len() > 2 {
+        return Err(Error::IncorrectNumberOfArguments(list.len(), 2));
+    }
 
     let result = eval(&list[0], env.clone())?;
     let path = result.get_string()?;
 
     let bytes = fs::read(Path::new(path)).map_err(|_| Error::FileError)?;
-    let wav = Wav::from_bytes(&bytes).map_err(|_| Error::WavError)?;
+    let mut buffer = read_wav(&bytes)?;
 
-    match wav.data {
-        Data::BitDepth8(samples) => Ok(normalize_u8(&samples)),
-        Data::BitDepth16(samples) => Ok(normalize_i16(&samples)),
-        Data::BitDepth24(samples) => Ok(normalize_i32(&samples)),
+    if let Some(channel) = list.get(1) {
+        buffer = match eval(channel, env.clone())? {
+            Blad::Atom(a) if a == ":left" => buffer.channel(0),
+            Blad::Atom(a) if a == ":right" && buffer.channels > 1 => buffer.channel(1),
+            Blad::Atom(a) if a == ":mix" => buffer.mixdown(),
+            Blad::Literal(Literal::I64(i)) if i >= 0 && (i as usize) < buffer.channels => {
+                buffer.channel(i as usize)
+            }
+            channel => return Err(Error::InvalidChannel(channel)),
+        };
     }
+
+    Ok(Blad::Buffer(buffer))
 }
 
-fn normalize_u8(samples: &[u8]) -> Blad {
-    let mut normalized = vec![];
+/// Number of frames in a buffer
+pub fn process_buffer_length(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
 
-    for s in samples {
-        let sample = (*s as f32 / u8::MAX as f32) - 1.0;
-        normalized.push(Blad::Literal(Literal::F32(sample)));
-    }
+    let result = eval(&list[0], env.clone())?;
+    let frames = result.get_buffer()?.frames();
 
-    Blad::List(normalized)
+    Ok(Blad::Literal(Literal::I64(frames as i64)))
 }
 
-fn normalize_i16(samples: &[i16]) -> Blad {
-    let mut normalized = vec![];
+/// Length of a buffer in seconds
+pub fn process_buffer_duration(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
 
-    for s in samples {
-        let sample = *s as f32 / i16::MAX as f32;
-        normalized.push(Blad::Literal(Literal::F32(sample)));
-    }
+    let result = eval(&list[0], env.clone())?;
+    let duration = result.get_buffer()?.duration();
 
-    Blad::List(normalized)
+    Ok(Blad::Literal(Literal::F32(duration)))
 }
 
-fn normalize_i32(samples: &[i32]) -> Blad {
-    let mut normalized = vec![];
+/// `(buffer-slice buffer start end)`, with `start` and `end` in frames
+pub fn process_buffer_slice(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 3)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let buffer = result.get_buffer()?;
+    let start = eval(&list[1], env.clone())?.get_usize()?;
+    let end = eval(&list[2], env.clone())?.get_usize()?;
+
+    Ok(Blad::Buffer(buffer.slice(start, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::wav::tests::wav;
+    use crate::core::wav::FORMAT_PCM;
+    use crate::core::{eval, parse};
+    use crate::{set_prelude, Buffer};
+
+    fn env_with_buffer() -> Arc<Mutex<Environment>> {
+        let (env, _) = Environment::new();
+        let env = set_prelude(Arc::new(Mutex::new(env))).unwrap();
+        let buffer = Buffer::new(vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5].into(), 4, 2);
+
+        env.lock()
+            .unwrap()
+            .set("buffer", Blad::Buffer(buffer))
+            .unwrap();
+
+        env
+    }
 
-    for s in samples {
-        let sample = *s as f32 / 8_388_607.0 as f32;
-        normalized.push(Blad::Literal(Literal::F32(sample)));
+    fn run_with_buffer(code: &str) -> Result<Blad, Error> {
+        eval(&parse(code)?[0], env_with_buffer())
     }
 
-    Blad::List(normalized)
+    #[test]
+    fn length_and_duration() {
+        assert_eq!(
+            run_with_buffer("(buffer-length buffer)").unwrap(),
+            Blad::Literal(Literal::I64(3))
+        );
+        assert_eq!(
+            run_with_buffer("(buffer-duration buffer)").unwrap(),
+            Blad::Literal(Literal::F32(0.75))
+        );
+    }
+
+    #[test]
+    fn slice() {
+        assert_eq!(
+            run_with_buffer("(buffer-slice buffer 1 2)").unwrap(),
+            Blad::Buffer(Buffer::new(vec![0.2, 0.3].into(), 4, 2))
+        );
+        assert_eq!(
+            run_with_buffer("(buffer-length (buffer-slice buffer 2 10))").unwrap(),
+            Blad::Literal(Literal::I64(1))
+        );
+    }
+
+    #[test]
+    fn load_channels() {
+        let path = std::env::temp_dir().join("blaadje_load_channels.wav");
+        let data: Vec<u8> = [0_i16, 16_384, 16_384, -16_384]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let bytes = wav(FORMAT_PCM, 2, 44_100, 16, &data);
+        std::fs::write(&path, bytes).unwrap();
+
+        let load = |channel: &str| {
+            let code = format!("(samples \"{}\" {})", path.display(), channel);
+            run_with_buffer(&code).unwrap()
+        };
+
+        assert_eq!(
+            load(""),
+            Blad::Buffer(Buffer::new(vec![0.0, 0.5, 0.5, -0.5].into(), 44_100, 2))
+        );
+        assert_eq!(
+            load(":right"),
+            Blad::Buffer(Buffer::new(vec![0.5, -0.5].into(), 44_100, 1))
+        );
+        assert_eq!(
+            load(":mix"),
+            Blad::Buffer(Buffer::new(vec![0.25, 0.0].into(), 44_100, 1))
+        );
+        assert!(matches!(
+            run_with_buffer(&format!("(samples \"{}\" 2)", path.display()))
+                .unwrap_err()
+                .root_cause(),
+            Error::InvalidChannel(_)
+        ));
+    }
+
+    #[test]
+    fn load_keeps_rate() {
+        let path = std::env::temp_dir().join("blaadje_load_keeps_rate.wav");
+        std::fs::write(&path, wav(FORMAT_PCM, 1, 48_000, 16, &[0, 64])).unwrap();
+
+        let code = format!("(samples \"{}\")", path.display());
+
+        assert_eq!(
+            run_with_buffer(&code).unwrap(),
+            Blad::Buffer(Buffer::new(vec![0.5].into(), 48_000, 1))
+        );
+    }
+
+    #[test]
+    fn load_mono_right() {
+        let path = std::env::temp_dir().join("blaadje_load_mono_right.wav");
+        std::fs::write(&path, wav(FORMAT_PCM, 1, 44_100, 16, &[0, 0])).unwrap();
+
+        let code = format!("(samples \"{}\" :right)", path.display());
+
+        assert!(matches!(
+            run_with_buffer(&code).unwrap_err().root_cause(),
+            Error::InvalidChannel(_)
+        ));
+    }
+
+    #[test]
+    fn not_a_buffer() {
+        assert!(matches!(
+            run_with_buffer("(buffer-length '(1 2))")
+                .unwrap_err()
+                .root_cause(),
+            Error::ExpectedBuffer(_)
+        ));
+    }
 }
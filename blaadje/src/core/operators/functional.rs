@@ -0,0 +1,209 @@
+use super::super::{apply, args, eval};
+use super::conditional::truthy;
+use super::equality::boolean;
+use super::list::{from_items, get_items, sort_by_key};
+use crate::{Blad, Environment, Error};
+use std::sync::{Arc, Mutex};
+
+pub fn process_map(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let (items, function) = items_and_function(list, env.clone())?;
+
+    let mapped = get_items(&items)?
+        .iter()
+        .map(|item| apply(&function, vec![item.clone()], env.clone()))
+        .collect::<Result<_, _>>()?;
+
+    Ok(from_items(mapped))
+}
+
+pub fn process_filter(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let (items, function) = items_and_function(list, env.clone())?;
+    let mut filtered = vec![];
+
+    for item in get_items(&items)? {
+        if truthy(&apply(&function, vec![item.clone()], env.clone())?) {
+            filtered.push(item.clone());
+        }
+    }
+
+    Ok(from_items(filtered))
+}
+
+/// `(fold items initial_value function)`, calls `(function accumulator item)`
+pub fn process_fold(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 3)?;
+
+    let items = eval(&list[0], env.clone())?;
+    let initial_value = eval(&list[1], env.clone())?;
+    let function = eval(&list[2], env.clone())?;
+
+    get_items(&items)?
+        .iter()
+        .try_fold(initial_value, |acc, item| {
+            apply(&function, vec![acc, item.clone()], env.clone())
+        })
+}
+
+/// Fold starting from the first item, `()` for an empty list
+pub fn process_reduce(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let (items, function) = items_and_function(list, env.clone())?;
+
+    match get_items(&items)? {
+        [] => Ok(Blad::Unit),
+        [first, rest @ ..] => rest.iter().try_fold(first.clone(), |acc, item| {
+            apply(&function, vec![acc, item.clone()], env.clone())
+        }),
+    }
+}
+
+/// Sort on the result of calling `function` on every item
+pub fn process_sort_by(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let (items, function) = items_and_function(list, env.clone())?;
+
+    let mut keyed = get_items(&items)?
+        .iter()
+        .map(|item| {
+            Ok((
+                apply(&function, vec![item.clone()], env.clone())?,
+                item.clone(),
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    sort_by_key(&mut keyed, |(key, _)| key)?;
+
+    Ok(from_items(
+        keyed.into_iter().map(|(_, item)| item).collect(),
+    ))
+}
+
+pub fn process_any(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let found = find(list, env)?;
+
+    Ok(boolean(found.is_some()))
+}
+
+pub fn process_all(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let (items, function) = items_and_function(list, env.clone())?;
+
+    for item in get_items(&items)? {
+        if !truthy(&apply(&function, vec![item.clone()], env.clone())?) {
+            return Ok(boolean(false));
+        }
+    }
+
+    Ok(boolean(true))
+}
+
+/// The first item `function` is true for, `()` when there is none
+pub fn process_find(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let found = find(list, env)?;
+
+    Ok(found.unwrap_or(Blad::Unit))
+}
+
+fn find(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Option<Blad>, Error> {
+    let (items, function) = items_and_function(list, env.clone())?;
+
+    for item in get_items(&items)? {
+        if truthy(&apply(&function, vec![item.clone()], env.clone())?) {
+            return Ok(Some(item.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn items_and_function(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<(Blad, Blad), Error> {
+    let items = eval(&list[0], env.clone())?;
+    let function = eval(&list[1], env)?;
+
+    Ok((items, function))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, Literal};
+
+    #[test]
+    fn fold_million() {
+        assert_eq!(
+            run("(fold (range 1000000) 0 +)").unwrap(),
+            Blad::Literal(Literal::I64(499999500000)),
+        );
+    }
+
+    #[test]
+    fn map_filter_empty() {
+        assert_eq!(run("(map '() (fn (x) x))").unwrap(), Blad::Unit);
+        assert_eq!(run("(filter '(1 2) (fn (x) false))").unwrap(), Blad::Unit);
+    }
+
+    #[test]
+    fn reduce() {
+        assert_eq!(
+            run("(reduce '(3 8 2) max)").unwrap(),
+            Blad::Literal(Literal::I64(8)),
+        );
+        assert_eq!(run("(reduce '() max)").unwrap(), Blad::Unit);
+    }
+
+    #[test]
+    fn sort_by() {
+        assert_eq!(
+            run("(sort-by '((:b 2) (:a 1) (:c 3)) (fn (pair) (nth pair 1)))").unwrap(),
+            run("'((:a 1) (:b 2) (:c 3))").unwrap(),
+        );
+    }
+
+    #[test]
+    fn any_all() {
+        assert_eq!(
+            run("(any? '(1 5 2) (fn (x) (> x 4)))").unwrap(),
+            Blad::Literal(Literal::I64(1)),
+        );
+        assert_eq!(
+            run("(any? '() (fn (x) true))").unwrap(),
+            Blad::Literal(Literal::I64(0)),
+        );
+        assert_eq!(
+            run("(all? '(1 5 2) (fn (x) (> x 0)))").unwrap(),
+            Blad::Literal(Literal::I64(1)),
+        );
+        assert_eq!(
+            run("(all? '(1 5 2) (fn (x) (> x 1)))").unwrap(),
+            Blad::Literal(Literal::I64(0)),
+        );
+    }
+
+    #[test]
+    fn find() {
+        assert_eq!(
+            run("(find '(1 5 2 7) (fn (x) (> x 4)))").unwrap(),
+            Blad::Literal(Literal::I64(5)),
+        );
+        assert_eq!(run("(find '(1 2) (fn (x) (> x 4)))").unwrap(), Blad::Unit);
+    }
+
+    #[test]
+    fn function_errors() {
+        let error = run("(map '(1 2) (fn (x) (head x)))").unwrap_err();
+
+        assert!(error.to_string().contains("in call to `<lambda>`"));
+    }
+}
@@ -6,7 +6,7 @@ use std::sync::{Arc, Mutex};
 pub fn process_lambda(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
     args(list, 2)?;
 
-    let params = &list[0];
+    let params = list[0].strip_spans();
     let body = &list[1];
 
     let closure = Environment::child_from(env.clone());
@@ -14,20 +14,23 @@ pub fn process_lambda(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Bla
     match params {
         Blad::Unit | Blad::List(_) | Blad::Symbol(_) => Ok(Blad::Lambda(
             closure,
-            Box::new(params.clone()),
+            Box::new(params),
             Box::new(body.clone()),
+            None,
         )),
         _ => Err(Error::IncorrectLambdaSyntax(Blad::List(list.to_vec()))),
     }
 }
 
+/// Evaluate the arguments and bind them in the environment the body runs in,
+/// the body is in tail position so `eval` evaluates it.
 pub fn process_lambda_call(
     closure: &Environment,
     params: &Blad,
-    body: &Blad,
+    name: &str,
     list: &[Blad],
     env: Arc<Mutex<Environment>>,
-) -> Result<Blad, Error> {
+) -> Result<Arc<Mutex<Environment>>, Error> {
     let inner_env = Arc::new(Mutex::new(closure.clone()));
 
     let values = {
@@ -36,9 +39,9 @@ pub fn process_lambda_call(
         eval(&Blad::List(inner), env.clone())?
     };
 
-    resolve_lets(params, &values, inner_env.clone())?;
+    resolve_lets(params, &values, inner_env.clone()).map_err(|e| e.in_call(name))?;
 
-    eval(&body, inner_env)
+    Ok(inner_env)
 }
 
 #[cfg(test)]
@@ -56,7 +59,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(2)),
+            Blad::Literal(Literal::I64(2)),
         );
     }
 
@@ -78,7 +81,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(6)),
+            Blad::Literal(Literal::I64(6)),
         );
     }
 
@@ -98,7 +101,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(144)),
+            Blad::Literal(Literal::I64(144)),
         );
     }
 
@@ -118,7 +121,27 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(10610209857723)),
+            Blad::Literal(Literal::I64(10610209857723)),
+        );
+    }
+
+    #[test]
+    fn deep_tail_recursion() {
+        assert_eq!(
+            run("
+                (do
+                    (let count (fn (n total)
+                        (if (= n 0)
+                            total
+                            (count (- n 1) (+ total 2))
+                        )
+                    ))
+
+                    (count 100000 0)
+                )
+            ")
+            .unwrap(),
+            Blad::Literal(Literal::I64(200000)),
         );
     }
 
@@ -126,10 +149,41 @@ mod tests {
     fn direct_call() {
         assert_eq!(
             run("((fn (x) (+ x 1)) 1)").unwrap(),
-            Blad::Literal(Literal::Usize(2)),
+            Blad::Literal(Literal::I64(2)),
         );
     }
 
+    #[test]
+    fn call_stack() {
+        let error = run("
+            (do
+                (let first (fn (items) (head items)))
+                (let wrapper (fn (x) (first x)))
+                (wrapper 1)
+            )
+        ")
+        .unwrap_err();
+
+        assert_eq!(
+            error.root_cause(),
+            &Error::ExpectedList(Blad::Literal(Literal::I64(1)))
+        );
+
+        let message = error.to_string();
+        let first = message.find("in call to `first`").unwrap();
+        let wrapper = message.find("in call to `wrapper`").unwrap();
+
+        assert!(first < wrapper);
+        assert!(message.contains("4 |                 (let wrapper (fn (x) (first x)))"));
+    }
+
+    #[test]
+    fn anonymous_call_stack() {
+        let error = run("((fn (x) (head x)) 1)").unwrap_err();
+
+        assert!(error.to_string().contains("in call to `<lambda>`"));
+    }
+
     #[test]
     fn destructure() {
         assert_eq!(
@@ -140,7 +194,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(60)),
+            Blad::Literal(Literal::I64(60)),
         );
     }
 }
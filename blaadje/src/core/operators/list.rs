@@ -1,5 +1,7 @@
-use super::super::{args, eval};
+use super::super::{args, args_min, eval};
+use super::equality::compare_values;
 use crate::{Blad, Environment, Error, Literal};
+use std::cmp::Ordering;
 use std::sync::{Arc, Mutex};
 
 pub fn process_list(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
@@ -52,10 +54,9 @@ pub fn process_cons(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad,
 
     match items {
         Blad::Unit => Ok(Blad::List(vec![item])),
-        Blad::List(l) => {
-            let mut new_items = vec![item];
-            new_items.extend_from_slice(&l);
-            Ok(Blad::List(new_items))
+        Blad::List(mut l) => {
+            l.insert(0, item);
+            Ok(Blad::List(l))
         }
         _ => Err(Error::ExpectedList(items)),
     }
@@ -67,21 +68,197 @@ pub fn process_append(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Bla
     let item = eval(&list[0], env.clone())?;
     let items = eval(&list[1], env.clone())?;
 
-    match (&item, &items) {
+    match (item, items) {
         (Blad::Literal(Literal::String(a)), Blad::Literal(Literal::String(b))) => {
-            let mut result = a.to_string();
-            result.push_str(b);
+            let mut result = a;
+            result.push_str(&b);
 
             Ok(Blad::Literal(Literal::String(result)))
         }
-        (_, Blad::List(l)) => {
-            let mut new_items = l.clone();
-            new_items.push(item);
+        (item, Blad::List(mut l)) => {
+            l.push(item);
 
-            Ok(Blad::List(new_items))
+            Ok(Blad::List(l))
         }
-        (_, Blad::Unit) => Ok(Blad::List(vec![item])),
-        _ => Err(Error::ExpectedList(items)),
+        (item, Blad::Unit) => Ok(Blad::List(vec![item])),
+        (_, items) => Err(Error::ExpectedList(items)),
+    }
+}
+
+pub fn process_length(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let length = get_items(&result)?.len();
+
+    Ok(Blad::Literal(Literal::I64(length as i64)))
+}
+
+pub fn process_nth(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let index = eval(&list[1], env.clone())?.get_i64()?;
+    let items = get_items(&result)?;
+
+    // Negative indices give the first item, out of range ones `()`
+    let item = items.get(index.max(0) as usize).cloned();
+
+    Ok(item.unwrap_or(Blad::Unit))
+}
+
+pub fn process_enumerate(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
+
+    let result = eval(&list[0], env.clone())?;
+
+    let enumerated = get_items(&result)?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| Blad::List(vec![Blad::Literal(Literal::I64(i as i64)), item.clone()]))
+        .collect();
+
+    Ok(from_items(enumerated))
+}
+
+/// `(range end)`, `(range start end)` or `(range start end step)`
+pub fn process_range(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args_min(list, 1)?;
+
+    let bounds = list
+        .iter()
+        .map(|b| eval(b, env.clone())?.get_i64())
+        .collect::<Result<Vec<i64>, Error>>()?;
+
+    let (start, end, step) = match bounds[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => return Err(Error::IncorrectNumberOfArguments(list.len(), 3)),
+    };
+
+    if step == 0 {
+        return Err(Error::InvalidRangeStep(step));
+    }
+
+    let mut items = vec![];
+    let mut i = start;
+
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        items.push(Blad::Literal(Literal::I64(i)));
+
+        i = match i.checked_add(step) {
+            Some(i) => i,
+            None => break,
+        };
+    }
+
+    Ok(from_items(items))
+}
+
+pub fn process_zip(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let a = eval(&list[0], env.clone())?;
+    let b = eval(&list[1], env.clone())?;
+
+    let zipped = get_items(&a)?
+        .iter()
+        .zip(get_items(&b)?.iter())
+        .map(|(a, b)| Blad::List(vec![a.clone(), b.clone()]))
+        .collect();
+
+    Ok(from_items(zipped))
+}
+
+pub fn process_reverse(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let mut items = get_items(&result)?.to_vec();
+    items.reverse();
+
+    Ok(from_items(items))
+}
+
+pub fn process_take(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let count = eval(&list[1], env.clone())?.get_usize()?;
+    let items = get_items(&result)?;
+
+    Ok(from_items(items[..count.min(items.len())].to_vec()))
+}
+
+pub fn process_drop(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let count = eval(&list[1], env.clone())?.get_usize()?;
+    let items = get_items(&result)?;
+
+    Ok(from_items(items[count.min(items.len())..].to_vec()))
+}
+
+pub fn process_concat(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    let mut items = vec![];
+
+    for b in list {
+        let result = eval(b, env.clone())?;
+        items.extend_from_slice(get_items(&result)?);
+    }
+
+    Ok(from_items(items))
+}
+
+pub fn process_sort(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
+
+    let result = eval(&list[0], env.clone())?;
+    let mut items = get_items(&result)?.to_vec();
+
+    sort_by_key(&mut items, |item| item)?;
+
+    Ok(from_items(items))
+}
+
+/// Stable sort on a key of every item, keys need to be numbers of one type
+pub fn sort_by_key<T, F>(items: &mut [T], key: F) -> Result<(), Error>
+where
+    F: Fn(&T) -> &Blad,
+{
+    let mut error = None;
+
+    items.sort_by(|a, b| match compare_values(key(a), key(b)) {
+        Ok(ordering) => ordering.unwrap_or(Ordering::Equal),
+        Err(e) => {
+            error.get_or_insert(e);
+            Ordering::Equal
+        }
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Items of a list, where `()` is the empty list
+pub fn get_items(blad: &Blad) -> Result<&[Blad], Error> {
+    match blad {
+        Blad::Unit => Ok(&[]),
+        Blad::List(items) => Ok(items),
+        _ => Err(Error::ExpectedList(blad.clone())),
+    }
+}
+
+/// Turn items into a list, where no items make `()`
+pub fn from_items(items: Vec<Blad>) -> Blad {
+    if items.is_empty() {
+        Blad::Unit
+    } else {
+        Blad::List(items)
     }
 }
 
@@ -95,10 +272,10 @@ mod tests {
         assert_eq!(
             run("(list 1 2 3 4)").unwrap(),
             Blad::List(vec![
-                Blad::Literal(Literal::Usize(1)),
-                Blad::Literal(Literal::Usize(2)),
-                Blad::Literal(Literal::Usize(3)),
-                Blad::Literal(Literal::Usize(4)),
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+                Blad::Literal(Literal::I64(4)),
             ])
         );
     }
@@ -112,7 +289,7 @@ mod tests {
     fn head() {
         assert_eq!(
             run("(head '(1 2 3 4))").unwrap(),
-            Blad::Literal(Literal::Usize(1))
+            Blad::Literal(Literal::I64(1))
         );
     }
 
@@ -126,9 +303,9 @@ mod tests {
         assert_eq!(
             run("(tail '(1 2 3 4))").unwrap(),
             Blad::List(vec![
-                Blad::Literal(Literal::Usize(2)),
-                Blad::Literal(Literal::Usize(3)),
-                Blad::Literal(Literal::Usize(4)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+                Blad::Literal(Literal::I64(4)),
             ])
         );
     }
@@ -143,10 +320,10 @@ mod tests {
         assert_eq!(
             run("(cons 1 '(2 3 4))").unwrap(),
             Blad::List(vec![
-                Blad::Literal(Literal::Usize(1)),
-                Blad::Literal(Literal::Usize(2)),
-                Blad::Literal(Literal::Usize(3)),
-                Blad::Literal(Literal::Usize(4)),
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+                Blad::Literal(Literal::I64(4)),
             ])
         );
     }
@@ -155,7 +332,7 @@ mod tests {
     fn cons_empty() {
         assert_eq!(
             run("(cons 1 '())").unwrap(),
-            Blad::List(vec![Blad::Literal(Literal::Usize(1))])
+            Blad::List(vec![Blad::Literal(Literal::I64(1))])
         );
     }
 
@@ -164,10 +341,10 @@ mod tests {
         assert_eq!(
             run("(append 1 '(2 3 4))").unwrap(),
             Blad::List(vec![
-                Blad::Literal(Literal::Usize(2)),
-                Blad::Literal(Literal::Usize(3)),
-                Blad::Literal(Literal::Usize(4)),
-                Blad::Literal(Literal::Usize(1)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+                Blad::Literal(Literal::I64(4)),
+                Blad::Literal(Literal::I64(1)),
             ])
         );
     }
@@ -180,11 +357,130 @@ mod tests {
         );
     }
 
+    #[test]
+    fn length() {
+        assert_eq!(run("(length '())").unwrap(), Blad::Literal(Literal::I64(0)));
+        assert_eq!(
+            run("(length (range 1000000))").unwrap(),
+            Blad::Literal(Literal::I64(1000000))
+        );
+    }
+
+    #[test]
+    fn nth() {
+        assert_eq!(
+            run("(nth '(1 2 3) -1)").unwrap(),
+            Blad::Literal(Literal::I64(1))
+        );
+        assert_eq!(run("(nth '() 0)").unwrap(), Blad::Unit);
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(
+            run("(range 3)").unwrap(),
+            Blad::List(vec![
+                Blad::Literal(Literal::I64(0)),
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+            ])
+        );
+
+        assert_eq!(
+            run("(range 10 4 -3)").unwrap(),
+            Blad::List(vec![
+                Blad::Literal(Literal::I64(10)),
+                Blad::Literal(Literal::I64(7)),
+            ])
+        );
+
+        assert_eq!(run("(range 4 2)").unwrap(), Blad::Unit);
+        assert_eq!(
+            run("(range 0 4 0)").unwrap_err().root_cause(),
+            &Error::InvalidRangeStep(0)
+        );
+    }
+
+    #[test]
+    fn zip() {
+        assert_eq!(
+            run("(zip '(1 2 3) '(:a :b))").unwrap(),
+            Blad::List(vec![
+                Blad::List(vec![
+                    Blad::Literal(Literal::I64(1)),
+                    Blad::Atom(":a".into())
+                ]),
+                Blad::List(vec![
+                    Blad::Literal(Literal::I64(2)),
+                    Blad::Atom(":b".into())
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn reverse() {
+        assert_eq!(
+            run("(reverse '(1 2 3))").unwrap(),
+            Blad::List(vec![
+                Blad::Literal(Literal::I64(3)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(1)),
+            ])
+        );
+    }
+
+    #[test]
+    fn take_drop() {
+        assert_eq!(
+            run("(take '(1 2 3) 2)").unwrap(),
+            Blad::List(vec![
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+            ])
+        );
+        assert_eq!(
+            run("(drop '(1 2 3) 2)").unwrap(),
+            Blad::List(vec![Blad::Literal(Literal::I64(3))])
+        );
+        assert_eq!(run("(drop '(1 2 3) 5)").unwrap(), Blad::Unit);
+        assert_eq!(run("(take '(1 2 3) 5)").unwrap(), run("'(1 2 3)").unwrap());
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(
+            run("(concat '(1) '() '(2 3))").unwrap(),
+            Blad::List(vec![
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+            ])
+        );
+        assert_eq!(run("(concat)").unwrap(), Blad::Unit);
+    }
+
+    #[test]
+    fn sort() {
+        assert_eq!(
+            run("(sort '(3 1 2))").unwrap(),
+            Blad::List(vec![
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+            ])
+        );
+        assert!(matches!(
+            run("(sort '(3 1.0))").unwrap_err().root_cause(),
+            Error::ExpectedSameTypes(_, _)
+        ));
+    }
+
     #[test]
     fn append_empty() {
         assert_eq!(
             run("(append 1 '())").unwrap(),
-            Blad::List(vec![Blad::Literal(Literal::Usize(1))])
+            Blad::List(vec![Blad::Literal(Literal::I64(1))])
         );
     }
 }
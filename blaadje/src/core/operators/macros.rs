@@ -6,14 +6,13 @@ use std::sync::{Arc, Mutex};
 pub fn process_macro(list: &[Blad], _env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
     args(list, 2)?;
 
-    let params = &list[0];
+    let params = list[0].strip_spans();
     let body = &list[1];
 
     match params {
-        Blad::Unit | Blad::List(_) | Blad::Symbol(_) => Ok(Blad::Macro(
-            Box::new(params.clone()),
-            Box::new(body.clone()),
-        )),
+        Blad::Unit | Blad::List(_) | Blad::Symbol(_) => {
+            Ok(Blad::Macro(Box::new(params), Box::new(body.clone()), None))
+        }
         _ => Err(Error::IncorrectMacroSyntax(Blad::List(list.to_vec()))),
     }
 }
@@ -25,21 +24,23 @@ pub fn expand_macro_call(
     env: Arc<Mutex<Environment>>,
 ) -> Result<Blad, Error> {
     let inner_env = Arc::new(Mutex::new(Environment::child_from(env.clone())));
-    let values = Blad::List(list.to_vec());
+    // Arguments are handed to the macro as plain data
+    let values = Blad::List(list.iter().map(Blad::strip_spans).collect());
 
     resolve_lets(params, &values, inner_env.clone())?;
 
     eval(&body, inner_env)
 }
 
+/// Expand the macro, the expansion is in tail position so `eval` evaluates it
 pub fn process_macro_call(
     params: &Blad,
     body: &Blad,
+    name: &str,
     list: &[Blad],
     env: Arc<Mutex<Environment>>,
 ) -> Result<Blad, Error> {
-    let output = expand_macro_call(params, body, list, env.clone())?;
-    eval(&output, env)
+    expand_macro_call(params, body, list, env).map_err(|e| e.in_call(name))
 }
 
 #[cfg(test)]
@@ -58,7 +59,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(2))
+            Blad::Literal(Literal::I64(2))
         );
     }
 
@@ -79,7 +80,20 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(16))
+            Blad::Literal(Literal::I64(16))
         );
     }
+
+    #[test]
+    fn call_stack() {
+        let error = run("
+            (do
+                (let swap (macro (a b) (list b a)))
+                (swap 1 2)
+            )
+        ")
+        .unwrap_err();
+
+        assert!(error.to_string().contains("in call to `swap`"));
+    }
 }
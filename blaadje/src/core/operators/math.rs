@@ -1,29 +1,80 @@
-use super::super::{args_min, eval};
+use super::super::{args, args_min, eval};
 use crate::{Blad, Environment, Error, Literal};
 use std::sync::{Arc, Mutex};
 
 pub fn process_add(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
-    args_min(list, 1)?;
+    fold_numbers(list, env, |a, b| overflow(a.checked_add(b)), |a, b| a + b)
+}
 
-    let result: Vec<Blad> = list
-        .iter()
-        .map(|b| eval(b, env.clone()))
-        .collect::<Result<_, _>>()?;
+pub fn process_subtract(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    fold_numbers(list, env, |a, b| overflow(a.checked_sub(b)), |a, b| a - b)
+}
 
-    match result[0] {
-        Blad::Literal(Literal::Usize(_)) => {
-            let sum = get_usizes(&result)?.iter().sum();
-            Ok(Blad::Literal(Literal::Usize(sum)))
-        }
-        Blad::Literal(Literal::F32(_)) => {
-            let sum = get_floats(&result)?.iter().sum();
-            Ok(Blad::Literal(Literal::F32(sum)))
+pub fn process_multiply(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    fold_numbers(list, env, |a, b| overflow(a.checked_mul(b)), |a, b| a * b)
+}
+
+pub fn process_divide(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    fold_numbers(
+        list,
+        env,
+        |a, b| match b {
+            0 => Err(Error::DivisionByZero),
+            _ => overflow(a.checked_div(b)),
+        },
+        |a, b| a / b,
+    )
+}
+
+pub fn process_modulo(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 2)?;
+
+    fold_numbers(
+        list,
+        env,
+        |a, b| match b {
+            0 => Err(Error::DivisionByZero),
+            _ => overflow(a.checked_rem(b)),
+        },
+        |a, b| a % b,
+    )
+}
+
+pub fn process_min(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    fold_numbers(list, env, |a, b| Ok(a.min(b)), f32::min)
+}
+
+pub fn process_max(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    fold_numbers(list, env, |a, b| Ok(a.max(b)), f32::max)
+}
+
+pub fn process_abs(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+    args(list, 1)?;
+
+    let result = eval(&list[0], env.clone())?;
+
+    match result {
+        Blad::Literal(Literal::I64(x)) => {
+            let abs = overflow(x.checked_abs())?;
+            Ok(Blad::Literal(Literal::I64(abs)))
         }
-        _ => Err(Error::ExpectedNumber(result[0].clone())),
+        Blad::Literal(Literal::F32(x)) => Ok(Blad::Literal(Literal::F32(x.abs()))),
+        _ => Err(Error::ExpectedNumber(result)),
     }
 }
 
-pub fn process_subtract(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
+fn overflow(result: Option<i64>) -> Result<i64, Error> {
+    result.ok_or(Error::IntegerOverflow)
+}
+
+/// Evaluate all arguments and fold them from left to right, all of them
+/// need to be of the same numeric type as the first one.
+fn fold_numbers(
+    list: &[Blad],
+    env: Arc<Mutex<Environment>>,
+    i64_op: fn(i64, i64) -> Result<i64, Error>,
+    f32_op: fn(f32, f32) -> f32,
+) -> Result<Blad, Error> {
     args_min(list, 1)?;
 
     let result: Vec<Blad> = list
@@ -32,27 +83,33 @@ pub fn process_subtract(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<B
         .collect::<Result<_, _>>()?;
 
     match result[0] {
-        Blad::Literal(Literal::Usize(_)) => {
-            let nums = get_usizes(&result)?;
-            let sum = nums.iter().skip(1).fold(nums[0], |acc, &x| acc - x);
-            Ok(Blad::Literal(Literal::Usize(sum)))
+        Blad::Literal(Literal::I64(_)) => {
+            let nums = get_integers(&result)?;
+            let value = nums
+                .iter()
+                .skip(1)
+                .try_fold(nums[0], |acc, &x| i64_op(acc, x))?;
+
+            Ok(Blad::Literal(Literal::I64(value)))
         }
         Blad::Literal(Literal::F32(_)) => {
             let nums = get_floats(&result)?;
-            let sum = nums.iter().skip(1).fold(nums[0], |acc, &x| acc - x);
-            Ok(Blad::Literal(Literal::F32(sum)))
+            let value = nums.iter().skip(1).fold(nums[0], |acc, &x| f32_op(acc, x));
+
+            Ok(Blad::Literal(Literal::F32(value)))
         }
         _ => Err(Error::ExpectedNumber(result[0].clone())),
     }
 }
 
-fn get_usizes(list: &[Blad]) -> Result<Vec<usize>, Error> {
+fn get_integers(list: &[Blad]) -> Result<Vec<i64>, Error> {
     let mut nums = vec![];
 
     for b in list {
         match b {
-            Blad::Literal(Literal::Usize(x)) => nums.push(*x),
-            _ => return Err(Error::ExpectedUsize(b.clone())),
+            Blad::Literal(Literal::I64(x)) => nums.push(*x),
+            Blad::Literal(_) => return Err(Error::ExpectedSameTypes(list[0].clone(), b.clone())),
+            _ => return Err(Error::ExpectedNumber(b.clone())),
         }
     }
 
@@ -65,7 +122,8 @@ fn get_floats(list: &[Blad]) -> Result<Vec<f32>, Error> {
     for b in list {
         match b {
             Blad::Literal(Literal::F32(x)) => nums.push(*x),
-            _ => return Err(Error::ExpectedUsize(b.clone())),
+            Blad::Literal(_) => return Err(Error::ExpectedSameTypes(list[0].clone(), b.clone())),
+            _ => return Err(Error::ExpectedNumber(b.clone())),
         }
     }
 
@@ -79,34 +137,133 @@ mod tests {
 
     #[test]
     fn addition() {
-        assert_eq!(run("(+ 0 2)").unwrap(), Blad::Literal(Literal::Usize(2)));
-        assert_eq!(run("(+ 1 2)").unwrap(), Blad::Literal(Literal::Usize(3)));
+        assert_eq!(run("(+ 0 2)").unwrap(), Blad::Literal(Literal::I64(2)));
+        assert_eq!(run("(+ 1 2)").unwrap(), Blad::Literal(Literal::I64(3)));
         assert_eq!(
             run("(+ 0 0 0 0 0 0 0 0 0)").unwrap(),
-            Blad::Literal(Literal::Usize(0))
+            Blad::Literal(Literal::I64(0))
         );
 
         assert_eq!(
             run("(+ 1 2 3 4 5 6 7 8 9 10)").unwrap(),
-            Blad::Literal(Literal::Usize(55))
+            Blad::Literal(Literal::I64(55))
         );
     }
 
     #[test]
     fn subtraction() {
+        assert_eq!(run("(- 100 58)").unwrap(), Blad::Literal(Literal::I64(42)));
+
         assert_eq!(
-            run("(- 100 58)").unwrap(),
-            Blad::Literal(Literal::Usize(42))
+            run("(- 55555 4444 1111)").unwrap(),
+            Blad::Literal(Literal::I64(50000))
         );
 
+        assert_eq!(run("(- 0 0 0 0)").unwrap(), Blad::Literal(Literal::I64(0)));
+    }
+
+    #[test]
+    fn negative() {
+        assert_eq!(run("(- 1 2)").unwrap(), Blad::Literal(Literal::I64(-1)));
+        assert_eq!(run("(+ -5 3)").unwrap(), Blad::Literal(Literal::I64(-2)));
         assert_eq!(
-            run("(- 55555 4444 1111)").unwrap(),
-            Blad::Literal(Literal::Usize(50000))
+            run("(* -0.5 2.0)").unwrap(),
+            Blad::Literal(Literal::F32(-1.0))
         );
+        assert_eq!(run("(abs -3)").unwrap(), Blad::Literal(Literal::I64(3)));
+    }
 
+    #[test]
+    fn integer_overflow() {
+        assert_eq!(
+            run("(+ 9223372036854775807 1)").unwrap_err().root_cause(),
+            &Error::IntegerOverflow
+        );
         assert_eq!(
-            run("(- 0 0 0 0)").unwrap(),
-            Blad::Literal(Literal::Usize(0))
+            run("(* 9223372036854775807 2)").unwrap_err().root_cause(),
+            &Error::IntegerOverflow
         );
+        assert_eq!(
+            run("(- -9223372036854775807 2)").unwrap_err().root_cause(),
+            &Error::IntegerOverflow
+        );
+    }
+
+    #[test]
+    fn multiplication() {
+        assert_eq!(run("(* 6 7)").unwrap(), Blad::Literal(Literal::I64(42)));
+        assert_eq!(run("(* 2 3 4)").unwrap(), Blad::Literal(Literal::I64(24)));
+        assert_eq!(
+            run("(* 0.5 120.0)").unwrap(),
+            Blad::Literal(Literal::F32(60.0))
+        );
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(run("(/ 42 6)").unwrap(), Blad::Literal(Literal::I64(7)));
+        assert_eq!(run("(/ 7 2)").unwrap(), Blad::Literal(Literal::I64(3)));
+        assert_eq!(
+            run("(/ 140.0 120.0 2.0)").unwrap(),
+            Blad::Literal(Literal::F32(140.0 / 120.0 / 2.0))
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(
+            run("(/ 42 0)").unwrap_err().root_cause(),
+            &Error::DivisionByZero
+        );
+        assert_eq!(
+            run("(mod 42 0)").unwrap_err().root_cause(),
+            &Error::DivisionByZero
+        );
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(run("(mod 42 5)").unwrap(), Blad::Literal(Literal::I64(2)));
+        assert_eq!(
+            run("(mod 5.5 2.0)").unwrap(),
+            Blad::Literal(Literal::F32(1.5))
+        );
+    }
+
+    #[test]
+    fn min_max() {
+        assert_eq!(run("(min 3 1 2)").unwrap(), Blad::Literal(Literal::I64(1)));
+        assert_eq!(run("(max 3 1 2)").unwrap(), Blad::Literal(Literal::I64(3)));
+        assert_eq!(
+            run("(max 0.2 0.8)").unwrap(),
+            Blad::Literal(Literal::F32(0.8))
+        );
+    }
+
+    #[test]
+    fn abs() {
+        assert_eq!(run("(abs 3)").unwrap(), Blad::Literal(Literal::I64(3)));
+        assert_eq!(
+            run("(abs (- 0.25 0.75))").unwrap(),
+            Blad::Literal(Literal::F32(0.5))
+        );
+    }
+
+    #[test]
+    fn mixed_types() {
+        assert!(matches!(
+            run("(+ 1 2.0)").unwrap_err().root_cause(),
+            Error::ExpectedSameTypes(_, _)
+        ));
+
+        assert!(matches!(
+            run("(* 2.0 3)").unwrap_err().root_cause(),
+            Error::ExpectedSameTypes(_, _)
+        ));
+
+        assert!(matches!(
+            run("(+ 1 :a)").unwrap_err().root_cause(),
+            Error::ExpectedNumber(_)
+        ));
     }
 }
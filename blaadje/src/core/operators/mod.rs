@@ -2,6 +2,7 @@ mod channel;
 mod conditional;
 mod equality;
 mod file;
+mod functional;
 mod lambda;
 mod list;
 mod macros;
@@ -12,10 +13,23 @@ mod variables;
 pub use channel::{process_call, process_cast};
 pub use conditional::process_if;
 pub use equality::{process_equal, process_greater_than, process_less_than};
-pub use file::process_samples;
+pub use file::{
+    process_buffer_duration, process_buffer_length, process_buffer_slice, process_samples,
+};
+pub use functional::{
+    process_all, process_any, process_filter, process_find, process_fold, process_map,
+    process_reduce, process_sort_by,
+};
 pub use lambda::{process_lambda, process_lambda_call};
-pub use list::{process_append, process_cons, process_head, process_list, process_tail};
+pub use list::{
+    process_append, process_concat, process_cons, process_drop, process_enumerate, process_head,
+    process_length, process_list, process_nth, process_range, process_reverse, process_sort,
+    process_tail, process_take, process_zip,
+};
 pub use macros::{process_macro, process_macro_call};
-pub use math::{process_add, process_subtract};
+pub use math::{
+    process_abs, process_add, process_divide, process_max, process_min, process_modulo,
+    process_multiply, process_subtract,
+};
 pub use string::process_string;
 pub use variables::process_let;
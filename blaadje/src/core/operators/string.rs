@@ -11,7 +11,7 @@ pub fn process_string(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Bla
         Blad::Atom(s) => Ok(s.clone()),
         Blad::Literal(Literal::String(s)) => Ok(s.clone()),
         Blad::Literal(Literal::F32(n)) => Ok(n.to_string()),
-        Blad::Literal(Literal::Usize(n)) => Ok(n.to_string()),
+        Blad::Literal(Literal::I64(n)) => Ok(n.to_string()),
         _ => Err(Error::UnableToConvertToString(result)),
     }?;
 
@@ -13,9 +13,10 @@ pub fn process_let(list: &[Blad], env: Arc<Mutex<Environment>>) -> Result<Blad,
 
 pub fn resolve_lets(key: &Blad, value: &Blad, env: Arc<Mutex<Environment>>) -> Result<Blad, Error> {
     match (key, value) {
+        (Blad::Spanned(_, key), _) => resolve_lets(key, value, env),
         (Blad::Symbol(key), _) => {
             let mut env = env.lock().unwrap();
-            env.set(key, value.clone())?;
+            env.set(key, named(key, value))?;
 
             Ok(Blad::Unit)
         }
@@ -40,6 +41,23 @@ pub fn resolve_lets(key: &Blad, value: &Blad, env: Arc<Mutex<Environment>>) -> R
     }
 }
 
+/// Name anonymous functions after the first symbol they are bound to, so they
+/// show up in call stacks.
+fn named(name: &str, value: &Blad) -> Blad {
+    match value {
+        Blad::Lambda(closure, params, body, None) => Blad::Lambda(
+            closure.clone(),
+            params.clone(),
+            body.clone(),
+            Some(name.to_string()),
+        ),
+        Blad::Macro(params, body, None) => {
+            Blad::Macro(params.clone(), body.clone(), Some(name.to_string()))
+        }
+        _ => value.clone(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,7 +75,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(66))
+            Blad::Literal(Literal::I64(66))
         );
     }
 
@@ -70,7 +88,8 @@ mod tests {
                     (let x 23)
                 )
             ")
-            .unwrap_err(),
+            .unwrap_err()
+            .root_cause(),
             Error::AttemptToRedefineVariable(_),
         ));
     }
@@ -86,7 +105,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(12))
+            Blad::Literal(Literal::I64(12))
         );
     }
 
@@ -101,7 +120,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(33))
+            Blad::Literal(Literal::I64(33))
         );
     }
 
@@ -116,7 +135,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(33))
+            Blad::Literal(Literal::I64(33))
         );
     }
 }
@@ -1,7 +1,10 @@
-use super::{Blad, Error, Keyword, Literal};
+use super::{Blad, Error, Keyword, Literal, Span};
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::Arc;
 
 pub fn parse(input: &str) -> Result<Vec<Blad>, Error> {
-    let tokens = tokenize(input);
+    let tokens = tokenize(input)?;
     let mut nodes = vec![];
     let mut index = 0;
 
@@ -18,24 +21,178 @@ pub fn parse(input: &str) -> Result<Vec<Blad>, Error> {
     Ok(nodes)
 }
 
-fn tokenize(input: &str) -> Vec<String> {
-    input
-        .replace("(", " ( ")
-        .replace(")", " ) ")
-        .replace("'", " ' ")
-        .split_whitespace()
-        .map(str::to_string)
-        .collect()
+struct Token {
+    text: String,
+    span: Span,
 }
 
-fn parse_tokens(tokens: &[String]) -> Result<(Blad, usize), Error> {
-    match tokens.get(0).map(|s| s.as_str()) {
-        Some("(") => {
+/// Walks over the characters of the source, keeping track of the line and
+/// column of the next character.
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    source: Arc<str>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+            source: Arc::from(input),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.line, self.column, self.source.clone())
+    }
+}
+
+impl<'a> Iterator for Cursor<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut cursor = Cursor::new(input);
+
+    loop {
+        let span = cursor.span();
+        let c = match cursor.next() {
+            Some(c) => c,
+            None => break,
+        };
+
+        match c {
+            '(' | ')' | '\'' => tokens.push(Token {
+                text: c.to_string(),
+                span,
+            }),
+            // Line comment
+            ';' => {
+                for c in cursor.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '#' if cursor.peek() == Some('|') => {
+                cursor.next();
+                skip_block_comment(&mut cursor).map_err(|e| e.at(&span))?;
+            }
+            '"' => {
+                let text = tokenize_string(&mut cursor).map_err(|e| e.at(&span))?;
+                tokens.push(Token { text, span });
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut text = c.to_string();
+
+                while let Some(c) = cursor.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '\'' | '"' | ';') {
+                        break;
+                    }
+
+                    text.push(c);
+                    cursor.next();
+                }
+
+                tokens.push(Token { text, span });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Skip a (nested) `#| |#` block comment, the opening `#|` is already consumed
+fn skip_block_comment(cursor: &mut Cursor) -> Result<(), Error> {
+    let mut depth = 1;
+
+    while depth > 0 {
+        match (cursor.next(), cursor.peek()) {
+            (Some('|'), Some('#')) => {
+                cursor.next();
+                depth -= 1;
+            }
+            (Some('#'), Some('|')) => {
+                cursor.next();
+                depth += 1;
+            }
+            (Some(_), _) => (),
+            (None, _) => return Err(Error::UnterminatedComment),
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a string literal up to the closing `"` and resolve its escapes, the
+/// token keeps its surrounding quotes so it can be told apart from symbols.
+fn tokenize_string(cursor: &mut Cursor) -> Result<String, Error> {
+    let mut token = "\"".to_string();
+
+    loop {
+        let span = cursor.span();
+
+        match cursor.next() {
+            Some('"') => break,
+            Some('\\') => match cursor.next() {
+                Some('n') => token.push('\n'),
+                Some('t') => token.push('\t'),
+                Some('"') => token.push('"'),
+                Some('\\') => token.push('\\'),
+                Some(c) => return Err(Error::InvalidEscape(c).at(&span)),
+                None => return Err(Error::UnterminatedString),
+            },
+            Some(c) => token.push(c),
+            None => return Err(Error::UnterminatedString),
+        }
+    }
+
+    token.push('"');
+
+    Ok(token)
+}
+
+fn parse_tokens(tokens: &[Token]) -> Result<(Blad, usize), Error> {
+    let token = match tokens.first() {
+        Some(token) => token,
+        None => return Ok((Blad::Unit, 0)),
+    };
+    let span = &token.span;
+
+    match token.text.as_str() {
+        "(" => {
             let mut blaadjes = vec![];
             // Start after the opening `(`
             let mut index = 1;
 
-            while tokens.get(index).ok_or(Error::ParseError(index))? != ")" {
+            while tokens
+                .get(index)
+                .ok_or_else(|| Error::UnclosedList.at(span))?
+                .text
+                != ")"
+            {
                 let (blad, steps) = parse_tokens(&tokens[index..tokens.len()])?;
                 blaadjes.push(blad);
                 index += steps;
@@ -45,47 +202,43 @@ fn parse_tokens(tokens: &[String]) -> Result<(Blad, usize), Error> {
             index += 1;
 
             if blaadjes.is_empty() {
-                Ok((Blad::Unit, index))
+                Ok((spanned(span, Blad::Unit), index))
             } else {
-                Ok((Blad::List(blaadjes), index))
+                Ok((spanned(span, Blad::List(blaadjes)), index))
             }
         }
-        Some(")") => Err(Error::UnexpectedToken(")".into())),
-        Some("'") => {
+        ")" => Err(Error::UnexpectedToken(")".into()).at(span)),
+        "'" => {
             let (blad, steps) = parse_tokens(&tokens[1..tokens.len()])?;
-            Ok((Blad::Quote(Box::new(blad)), steps + 1))
+            // Quoted code is used as data, which should not carry positions
+            let quote = Blad::Quote(Box::new(blad.strip_spans()));
+
+            Ok((spanned(span, quote), steps + 1))
+        }
+        t => {
+            let blad = parse_token(t).map_err(|e| e.at(span))?;
+            Ok((spanned(span, blad), 1))
         }
-        Some(t) => Ok((parse_token(&t)?, 1)),
-        None => Ok((Blad::Unit, 0)),
     }
 }
 
+fn spanned(span: &Span, blad: Blad) -> Blad {
+    Blad::Spanned(span.clone(), Box::new(blad))
+}
+
 fn parse_token(token: &str) -> Result<Blad, Error> {
-    // Numbers
-    if token.chars().next().unwrap().is_numeric() {
+    // Numbers, optionally negative
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    if digits.chars().next().map_or(false, char::is_numeric) {
         return parse_token_numeric(token);
     }
 
-    // Keywords / Symbols
+    // Special forms / Symbols
     match token {
-        "+" => Ok(Blad::Keyword(Keyword::Add)),
-        "-" => Ok(Blad::Keyword(Keyword::Subtract)),
-        "<" => Ok(Blad::Keyword(Keyword::LessThan)),
-        "=" => Ok(Blad::Keyword(Keyword::Equal)),
-        ">" => Ok(Blad::Keyword(Keyword::GreaterThan)),
-        "append" => Ok(Blad::Keyword(Keyword::Append)),
-        "call" => Ok(Blad::Keyword(Keyword::Call)),
-        "cast" => Ok(Blad::Keyword(Keyword::Cast)),
-        "cons" => Ok(Blad::Keyword(Keyword::Cons)),
         "fn" => Ok(Blad::Keyword(Keyword::Lambda)),
-        "head" => Ok(Blad::Keyword(Keyword::Head)),
         "if" => Ok(Blad::Keyword(Keyword::If)),
         "let" => Ok(Blad::Keyword(Keyword::Let)),
-        "list" => Ok(Blad::Keyword(Keyword::List)),
         "macro" => Ok(Blad::Keyword(Keyword::Macro)),
-        "samples" => Ok(Blad::Keyword(Keyword::Samples)),
-        "string" => Ok(Blad::Keyword(Keyword::String)),
-        "tail" => Ok(Blad::Keyword(Keyword::Tail)),
         s if s.starts_with(':') => Ok(Blad::Atom(token.to_owned())),
         s if s.starts_with('"') && s.ends_with('"') => {
             let mut string = s.to_owned();
@@ -109,11 +262,11 @@ fn parse_token_numeric(token: &str) -> Result<Blad, Error> {
 
         Ok(Blad::Literal(Literal::F32(float)))
     } else {
-        let int: usize = token
+        let int: i64 = token
             .parse()
             .map_err(|_| Error::UnsupportedNumericType(token.into()))?;
 
-        Ok(Blad::Literal(Literal::Usize(int)))
+        Ok(Blad::Literal(Literal::I64(int)))
     }
 }
 
@@ -121,31 +274,98 @@ fn parse_token_numeric(token: &str) -> Result<Blad, Error> {
 mod tests {
     use super::*;
 
+    fn texts(input: &str) -> Result<Vec<String>, Error> {
+        Ok(tokenize(input)?.into_iter().map(|t| t.text).collect())
+    }
+
     #[test]
     fn tokenize_input() {
         assert_eq!(
-            tokenize("(+ 1 (- 3 4))"),
+            texts("(+ 1 (- 3 4))").unwrap(),
             ["(", "+", "1", "(", "-", "3", "4", ")", ")"]
         );
 
         assert_eq!(
-            tokenize("('y '(+ 1 2))"),
+            texts("('y '(+ 1 2))").unwrap(),
             ["(", "'", "y", "'", "(", "+", "1", "2", ")", ")"]
         );
     }
 
+    #[test]
+    fn tokenize_comments() {
+        assert_eq!(
+            texts("; a comment\n(+ 1 2) ; (+ 3 4)").unwrap(),
+            ["(", "+", "1", "2", ")"]
+        );
+
+        assert_eq!(
+            texts("(+ 1 #| two #| nested |# |# 3)").unwrap(),
+            ["(", "+", "1", "3", ")"]
+        );
+
+        assert_eq!(
+            texts("(+ 1 #| never closed").unwrap_err().root_cause(),
+            &Error::UnterminatedComment
+        );
+    }
+
+    #[test]
+    fn tokenize_strings() {
+        assert_eq!(
+            texts("(samples \"./my samples/kick (1).wav\")").unwrap(),
+            ["(", "samples", "\"./my samples/kick (1).wav\"", ")"]
+        );
+
+        assert_eq!(
+            texts(r#"(list "it's" "a \"quote\"\n" "; not a comment")"#).unwrap(),
+            [
+                "(",
+                "list",
+                "\"it's\"",
+                "\"a \"quote\"\n\"",
+                "\"; not a comment\"",
+                ")"
+            ]
+        );
+
+        assert_eq!(
+            texts("\"never closed").unwrap_err().root_cause(),
+            &Error::UnterminatedString
+        );
+        assert_eq!(
+            texts(r#""\q""#).unwrap_err().root_cause(),
+            &Error::InvalidEscape('q')
+        );
+    }
+
     #[test]
     fn parse() {
         let input = "(+ 1 4)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
+        let (ast, _) = parse_tokens(&tokens).unwrap();
+
+        assert_eq!(
+            ast,
+            Blad::List(vec![
+                Blad::Symbol("+".into()),
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(4)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn parse_negative() {
+        let input = "(- -1 -0.5)";
+        let tokens = tokenize(input).unwrap();
         let (ast, _) = parse_tokens(&tokens).unwrap();
 
         assert_eq!(
             ast,
             Blad::List(vec![
-                Blad::Keyword(Keyword::Add),
-                Blad::Literal(Literal::Usize(1)),
-                Blad::Literal(Literal::Usize(4)),
+                Blad::Symbol("-".into()),
+                Blad::Literal(Literal::I64(-1)),
+                Blad::Literal(Literal::F32(-0.5)),
             ]),
         );
     }
@@ -153,7 +373,7 @@ mod tests {
     #[test]
     fn parse_quote() {
         let input = "(let x '(+ 2 3))";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let (ast, _) = parse_tokens(&tokens).unwrap();
 
         assert_eq!(
@@ -162,18 +382,66 @@ mod tests {
                 Blad::Keyword(Keyword::Let),
                 Blad::Symbol("x".into()),
                 Blad::Quote(Box::new(Blad::List(vec![
-                    Blad::Keyword(Keyword::Add),
-                    Blad::Literal(Literal::Usize(2)),
-                    Blad::Literal(Literal::Usize(3)),
+                    Blad::Symbol("+".into()),
+                    Blad::Literal(Literal::I64(2)),
+                    Blad::Literal(Literal::I64(3)),
                 ]))),
             ]),
         );
     }
 
+    #[test]
+    fn parse_string() {
+        assert_eq!(
+            super::parse(r#"(append "hello world" "\n")"#).unwrap(),
+            vec![Blad::List(vec![
+                Blad::Symbol("append".into()),
+                Blad::Literal(Literal::String("hello world".into())),
+                Blad::Literal(Literal::String("\n".into())),
+            ])],
+        );
+    }
+
+    #[test]
+    fn parse_spans() {
+        let ast = super::parse("(+ 1\n   (foo 'bar))").unwrap();
+
+        match &ast[0] {
+            Blad::Spanned(span, blad) => {
+                assert_eq!((span.line, span.column), (1, 1));
+
+                match blad.get_list().unwrap()[2] {
+                    Blad::Spanned(ref span, _) => {
+                        assert_eq!((span.line, span.column), (2, 4));
+                        assert_eq!(span.source_line(), "   (foo 'bar))");
+                    }
+                    _ => panic!("expected a span"),
+                }
+            }
+            _ => panic!("expected a span"),
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        match super::parse("(+ 1\n  (- 2 3)").unwrap_err() {
+            Error::At(span, error) => {
+                assert_eq!((span.line, span.column), (1, 1));
+                assert_eq!(*error, Error::UnclosedList);
+            }
+            error => panic!("expected a span, got {:?}", error),
+        }
+
+        match super::parse("(+ 1 2))").unwrap_err() {
+            Error::At(span, _) => assert_eq!((span.line, span.column), (1, 8)),
+            error => panic!("expected a span, got {:?}", error),
+        }
+    }
+
     #[test]
     fn parse_quoted_symbol() {
         let input = "('x)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let (ast, _) = parse_tokens(&tokens).unwrap();
 
         assert_eq!(
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Position of a node in the source it was parsed from, lines and columns
+/// start at 1.
+#[derive(Clone)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    source: Arc<str>,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, source: Arc<str>) -> Self {
+        Self {
+            line,
+            column,
+            source,
+        }
+    }
+
+    /// The full source line this span points into
+    pub fn source_line(&self) -> &str {
+        self.source.lines().nth(self.line - 1).unwrap_or("")
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, other: &Self) -> bool {
+        self.line == other.line && self.column == other.column
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Span({}:{})", self.line, self.column)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Keep tabs so the caret lines up with the source line
+        let indent: String = self
+            .source_line()
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(
+            f,
+            "{}--> line {}, column {}",
+            gutter, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source_line())?;
+        write!(f, "{} | {}^", gutter, indent)
+    }
+}
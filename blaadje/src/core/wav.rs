@@ -0,0 +1,192 @@
+use super::{Buffer, Error};
+
+pub(crate) const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+struct Format {
+    format: u16,
+    channels: usize,
+    sample_rate: usize,
+    bit_depth: u16,
+}
+
+/// Decode a RIFF WAV file to a buffer of samples between -1 and 1.
+///
+/// Supports 8, 16, 24 and 32 bit integer PCM and 32 and 64 bit float data.
+pub fn read_wav(bytes: &[u8]) -> Result<Buffer, Error> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(Error::WavError);
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut position = 12;
+
+    while position + 8 <= bytes.len() {
+        let id = &bytes[position..position + 4];
+        let size = u32::from_le_bytes(bytes[position + 4..position + 8].try_into().unwrap());
+        let start = position + 8;
+        // Some writers get the size of the last chunk wrong
+        let end = start.saturating_add(size as usize).min(bytes.len());
+
+        match id {
+            b"fmt " => format = Some(read_format(&bytes[start..end])?),
+            b"data" => data = Some(&bytes[start..end]),
+            _ => (),
+        }
+
+        // Chunks are padded to an even size
+        position = end + (size as usize & 1);
+    }
+
+    let format = format.ok_or(Error::WavError)?;
+    let data = data.ok_or(Error::WavError)?;
+
+    let samples: Vec<f32> = match (format.format, format.bit_depth) {
+        (FORMAT_PCM, 8) => data.iter().map(|s| (*s as f32 - 128.0) / 128.0).collect(),
+        (FORMAT_PCM, 16) => data
+            .chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32_768.0)
+            .collect(),
+        (FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            // Shift into the top of an i32 to extend the sign
+            .map(|s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8_388_608.0)
+            .collect(),
+        (FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        (FORMAT_FLOAT, 32) => data
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect(),
+        (FORMAT_FLOAT, 64) => data
+            .chunks_exact(8)
+            .map(|s| f64::from_le_bytes(s.try_into().unwrap()) as f32)
+            .collect(),
+        _ => return Err(Error::WavError),
+    };
+
+    Ok(Buffer::new(
+        samples.into(),
+        format.sample_rate,
+        format.channels,
+    ))
+}
+
+fn read_format(chunk: &[u8]) -> Result<Format, Error> {
+    if chunk.len() < 16 {
+        return Err(Error::WavError);
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([chunk[i], chunk[i + 1]]);
+    let mut format = u16_at(0);
+
+    // The actual format is the first two bytes of the sub format GUID
+    if format == FORMAT_EXTENSIBLE {
+        if chunk.len() < 26 {
+            return Err(Error::WavError);
+        }
+
+        format = u16_at(24);
+    }
+
+    let channels = u16_at(2) as usize;
+    let sample_rate = u32::from_le_bytes(chunk[4..8].try_into().unwrap()) as usize;
+
+    if channels == 0 || sample_rate == 0 {
+        return Err(Error::WavError);
+    }
+
+    Ok(Format {
+        format,
+        channels,
+        sample_rate,
+        bit_depth: u16_at(14),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a WAV file in memory, shared with the tests loading files
+    pub(crate) fn wav(
+        format: u16,
+        channels: u16,
+        sample_rate: u32,
+        bit_depth: u16,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16_u32.to_le_bytes());
+        bytes.extend_from_slice(&format.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * (channels * bit_depth / 8) as u32).to_le_bytes());
+        bytes.extend_from_slice(&(channels * bit_depth / 8).to_le_bytes());
+        bytes.extend_from_slice(&bit_depth.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+
+    #[test]
+    fn pcm_8() {
+        let buffer = read_wav(&wav(FORMAT_PCM, 1, 8000, 8, &[0, 128, 255])).unwrap();
+
+        assert_eq!(&buffer.samples[..], &[-1.0, 0.0, 127.0 / 128.0]);
+        assert_eq!(buffer.sample_rate, 8000);
+    }
+
+    #[test]
+    fn pcm_16_stereo() {
+        let data: Vec<u8> = [i16::MIN, 16_384, 0, -16_384]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let buffer = read_wav(&wav(FORMAT_PCM, 2, 44_100, 16, &data)).unwrap();
+
+        assert_eq!(buffer.channels, 2);
+        assert_eq!(buffer.frames(), 2);
+        assert_eq!(buffer.get(0, 0), -1.0);
+        assert_eq!(buffer.get(0, 1), 0.5);
+        assert_eq!(buffer.get(1, 1), -0.5);
+    }
+
+    #[test]
+    fn pcm_24() {
+        let buffer = read_wav(&wav(FORMAT_PCM, 1, 48_000, 24, &[0, 0, 0x80, 0, 0, 0x40])).unwrap();
+
+        assert_eq!(&buffer.samples[..], &[-1.0, 0.5]);
+    }
+
+    #[test]
+    fn float_32() {
+        let data: Vec<u8> = [0.25_f32, -0.75]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let buffer = read_wav(&wav(FORMAT_FLOAT, 1, 48_000, 32, &data)).unwrap();
+
+        assert_eq!(&buffer.samples[..], &[0.25, -0.75]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(read_wav(b"RIFF"), Err(Error::WavError));
+        assert_eq!(
+            read_wav(&wav(FORMAT_FLOAT, 1, 48_000, 8, &[0])),
+            Err(Error::WavError)
+        );
+    }
+}
@@ -4,8 +4,11 @@ mod audio;
 mod core;
 mod prelude;
 
-pub use audio::{Engine, System};
-pub use core::{eval, eval_nodes, parse, Blad, Channel, Environment, Error, Literal, Screech};
+pub use audio::{Engine, NullSystem, System, BLOCK_SIZE, CHANNELS};
+pub use core::{
+    eval, eval_nodes, parse, Blad, Buffer, Channel, ChannelReceiver, Environment, Error, Literal,
+    Message, Screech, Span,
+};
 pub use prelude::set_prelude;
 
 pub fn run(code: &str) -> Result<Blad, Error> {
@@ -1,6 +1,53 @@
-use crate::{eval_nodes, parse, Environment, Error};
+use crate::core::Keyword;
+use crate::{eval_nodes, parse, Blad, Environment, Error};
 use std::sync::{Arc, Mutex};
 
+/// Native functions, bound by name like the rest of the prelude. Only the
+/// special forms (`fn`, `if`, `let` and `macro`) are parsed as keywords.
+const BUILTINS: [Keyword; 41] = [
+    Keyword::Abs,
+    Keyword::Add,
+    Keyword::All,
+    Keyword::Any,
+    Keyword::Append,
+    Keyword::BufferDuration,
+    Keyword::BufferLength,
+    Keyword::BufferSlice,
+    Keyword::Call,
+    Keyword::Cast,
+    Keyword::Concat,
+    Keyword::Cons,
+    Keyword::Divide,
+    Keyword::Drop,
+    Keyword::Enumerate,
+    Keyword::Equal,
+    Keyword::Filter,
+    Keyword::Find,
+    Keyword::Fold,
+    Keyword::GreaterThan,
+    Keyword::Head,
+    Keyword::Length,
+    Keyword::LessThan,
+    Keyword::List,
+    Keyword::Map,
+    Keyword::Max,
+    Keyword::Min,
+    Keyword::Modulo,
+    Keyword::Multiply,
+    Keyword::Nth,
+    Keyword::Range,
+    Keyword::Reduce,
+    Keyword::Reverse,
+    Keyword::Samples,
+    Keyword::Sort,
+    Keyword::SortBy,
+    Keyword::String,
+    Keyword::Subtract,
+    Keyword::Tail,
+    Keyword::Take,
+    Keyword::Zip,
+];
+
 const PRELUDE: &'static str = "
     (let false 0)
     (let true 1)
@@ -15,55 +62,7 @@ const PRELUDE: &'static str = "
 
     (let empty? (fn (l) (= l ())))
 
-    (let fold (fn (items initial_value function)
-        (if (empty? items)
-            initial_value
-            (fold
-                (tail items)
-                (function initial_value (head items))
-                function
-            )
-        )
-    ))
-
     (let do (fn args (fold args '() (fn (xs x) x))))
-
-    (let length (fn (items) (do
-        (let f (fn (items index)
-            (if (empty? items) index (f (tail items) (+ index 1)))
-        ))
-
-        (f items 0)
-    )))
-
-    (let nth (fn (items index)
-        (if (<= index 0)
-            (head items)
-            (nth (tail items) (- index 1))
-        )
-    ))
-
-    (let map (fn (items f)
-        (fold items '() (fn (xs x)
-            (append (f x) xs)
-        ))
-    ))
-
-    (let filter (fn (items f)
-        (fold items '() (fn (xs x)
-            (if (f x) (append x xs) xs)
-        ))
-    ))
-
-    (let enumerate (fn (items) (do
-        (let result (fold items '( () 0) (fn ((items index) item) (do
-            (list
-                (append (list index item) items)
-                (+ index 1)
-            )
-        ))))
-        (head result)
-    )))
 ";
 
 const SCREECH_PRELUDE: &'static str = "
@@ -101,12 +100,24 @@ const SCREECH_PRELUDE: &'static str = "
     (let Midi.new (fn (id)
         (call (list :insert_module :midi id))))
 
+    (let Mixer.new (fn args
+        (call (concat (list :insert_module :mixer) args))))
+
     (let Vca.new (fn (id)
         (call (list :insert_module :vca id))))
 
     (let Filter.new (fn (id)
         (call (list :insert_module :filter id))))
 
+    (let Delay.new (fn args
+        (call (concat (list :insert_module :delay) args))))
+
+    (let Envelope.new (fn (id)
+        (call (list :insert_module :envelope id))))
+
+    (let Reverb.new (fn (id)
+        (call (list :insert_module :reverb id))))
+
     (let Sample.new (fn (id)
         (call (list :insert_module :sample id))))
 
@@ -127,8 +138,17 @@ const SCREECH_PRELUDE: &'static str = "
 ";
 
 pub fn set_prelude(env: Arc<Mutex<Environment>>) -> Result<Arc<Mutex<Environment>>, Error> {
-    eval_nodes(&parse(PRELUDE)?, env.clone())?;
-    eval_nodes(&parse(SCREECH_PRELUDE)?, env.clone())?;
+    // Programs can define their own versions of everything in the prelude,
+    // without changing what the prelude itself uses
+    let prelude = env.lock().unwrap().insert_parent();
+
+    for keyword in BUILTINS {
+        let mut prelude = prelude.lock().unwrap();
+        prelude.set(&keyword.to_string(), Blad::Keyword(keyword))?;
+    }
+
+    eval_nodes(&parse(PRELUDE)?, prelude.clone())?;
+    eval_nodes(&parse(SCREECH_PRELUDE)?, prelude)?;
 
     Ok(env)
 }
@@ -139,22 +159,61 @@ mod tests {
 
     #[test]
     fn empty() {
-        assert_eq!(
-            run("(empty? '())").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
-        );
+        assert_eq!(run("(empty? '())").unwrap(), Blad::Literal(Literal::I64(1)),);
     }
 
     #[test]
     fn non_empty() {
         assert_eq!(
             run("(empty? '(1 2 3))").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
         );
 
         assert_eq!(
             run("(empty? '(() () ()))").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
+        );
+    }
+
+    #[test]
+    fn fold_long_list() {
+        let items: Vec<String> = (1..=2_000).map(|i| i.to_string()).collect();
+        let code = format!("(fold '({}) 0 +)", items.join(" "));
+
+        assert_eq!(run(&code).unwrap(), Blad::Literal(Literal::I64(2_001_000)));
+    }
+
+    #[test]
+    fn shadow_builtins() {
+        assert_eq!(
+            run("((fn (range) range) 3)").unwrap(),
+            Blad::Literal(Literal::I64(3)),
+        );
+
+        assert_eq!(
+            run("(let length 2) (let find (fn (x) x)) (find length)").unwrap(),
+            Blad::Literal(Literal::I64(2)),
+        );
+
+        // The prelude keeps working with a shadowed builtin
+        assert_eq!(
+            run("(let fold 0) (do 1 2)").unwrap(),
+            Blad::Literal(Literal::I64(2)),
+        );
+
+        assert_eq!(
+            run("(let abs (fn (x) x)) ((fn (max mod) (+ max mod)) 1 2) (abs -3)").unwrap(),
+            Blad::Literal(Literal::I64(-3)),
+        );
+
+        assert_eq!(
+            run("(let buffer-length 4) (let + -) (+ buffer-length 1)").unwrap(),
+            Blad::Literal(Literal::I64(3)),
+        );
+
+        assert_eq!(
+            run("(let < 0) (<= 1 2)").unwrap(),
+            Blad::Literal(Literal::I64(1)),
         );
     }
 
@@ -162,22 +221,22 @@ mod tests {
     fn or() {
         assert_eq!(
             run("(or true true)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
 
         assert_eq!(
             run("(or true false)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
 
         assert_eq!(
             run("(or false true)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
 
         assert_eq!(
             run("(or false false)").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
         );
     }
 
@@ -185,22 +244,22 @@ mod tests {
     fn and() {
         assert_eq!(
             run("(and true true)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
 
         assert_eq!(
             run("(and true false)").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
         );
 
         assert_eq!(
             run("(and false true)").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
         );
 
         assert_eq!(
             run("(and false false)").unwrap(),
-            Blad::Literal(Literal::Usize(0)),
+            Blad::Literal(Literal::I64(0)),
         );
     }
 
@@ -208,7 +267,7 @@ mod tests {
     fn length() {
         assert_eq!(
             run("(length '(1 2 3 4))").unwrap(),
-            Blad::Literal(Literal::Usize(4)),
+            Blad::Literal(Literal::I64(4)),
         );
     }
 
@@ -216,12 +275,12 @@ mod tests {
     fn nth() {
         assert_eq!(
             run("(nth '(1 2 3 4) 0)").unwrap(),
-            Blad::Literal(Literal::Usize(1)),
+            Blad::Literal(Literal::I64(1)),
         );
 
         assert_eq!(
             run("(nth '(1 2 3 4) 2)").unwrap(),
-            Blad::Literal(Literal::Usize(3)),
+            Blad::Literal(Literal::I64(3)),
         );
 
         assert_eq!(run("(nth '(1 2 3 4) 100)").unwrap(), Blad::Unit);
@@ -231,7 +290,7 @@ mod tests {
     fn fold() {
         assert_eq!(
             run("(fold '(1 2 3 4) 0 +)").unwrap(),
-            Blad::Literal(Literal::Usize(10)),
+            Blad::Literal(Literal::I64(10)),
         );
     }
 
@@ -240,10 +299,10 @@ mod tests {
         assert_eq!(
             run("(map '(0 1 2 3) (fn (x) (+ x 1)))").unwrap(),
             Blad::List(vec![
-                Blad::Literal(Literal::Usize(1)),
-                Blad::Literal(Literal::Usize(2)),
-                Blad::Literal(Literal::Usize(3)),
-                Blad::Literal(Literal::Usize(4)),
+                Blad::Literal(Literal::I64(1)),
+                Blad::Literal(Literal::I64(2)),
+                Blad::Literal(Literal::I64(3)),
+                Blad::Literal(Literal::I64(4)),
             ])
         );
     }
@@ -253,8 +312,8 @@ mod tests {
         assert_eq!(
             run("(filter '(8 2 6 3) (fn (x) (> x 4)))").unwrap(),
             Blad::List(vec![
-                Blad::Literal(Literal::Usize(8)),
-                Blad::Literal(Literal::Usize(6)),
+                Blad::Literal(Literal::I64(8)),
+                Blad::Literal(Literal::I64(6)),
             ])
         );
     }
@@ -265,20 +324,20 @@ mod tests {
             run("(enumerate '(8 2 6 3))").unwrap(),
             Blad::List(vec![
                 Blad::List(vec![
-                    Blad::Literal(Literal::Usize(0)),
-                    Blad::Literal(Literal::Usize(8)),
+                    Blad::Literal(Literal::I64(0)),
+                    Blad::Literal(Literal::I64(8)),
                 ]),
                 Blad::List(vec![
-                    Blad::Literal(Literal::Usize(1)),
-                    Blad::Literal(Literal::Usize(2)),
+                    Blad::Literal(Literal::I64(1)),
+                    Blad::Literal(Literal::I64(2)),
                 ]),
                 Blad::List(vec![
-                    Blad::Literal(Literal::Usize(2)),
-                    Blad::Literal(Literal::Usize(6)),
+                    Blad::Literal(Literal::I64(2)),
+                    Blad::Literal(Literal::I64(6)),
                 ]),
                 Blad::List(vec![
-                    Blad::Literal(Literal::Usize(3)),
-                    Blad::Literal(Literal::Usize(3)),
+                    Blad::Literal(Literal::I64(3)),
+                    Blad::Literal(Literal::I64(3)),
                 ]),
             ])
         );
@@ -295,7 +354,7 @@ mod tests {
                 )
             ")
             .unwrap(),
-            Blad::Literal(Literal::Usize(6))
+            Blad::Literal(Literal::I64(6))
         );
     }
 
@@ -1,7 +1,11 @@
 mod system;
+mod wav;
 
-use blaadje::{run_with_env, set_prelude, Engine, Environment};
-use clap::{Arg, Command};
+use blaadje::{
+    run_with_env, set_prelude, Blad, ChannelReceiver, Engine, Environment, NullSystem, BLOCK_SIZE,
+    CHANNELS,
+};
+use clap::{value_parser, Arg, ArgMatches, Command};
 use notify::{
     event::{AccessKind, AccessMode},
     recommended_watcher, Event, EventKind, RecursiveMode, Result as NotifyResult, Watcher,
@@ -14,7 +18,14 @@ use std::path::Path;
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use system::Sys;
+use wav::write_wav;
+
+const SAMPLE_RATE: usize = 44_100;
+
+/// How long rendering waits for a message before checking if the program is done
+const RENDER_POLL: Duration = Duration::from_millis(10);
 
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = Command::new("blaadje-cli")
@@ -30,6 +41,49 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .about("Interactive file mode")
                 .arg(Arg::new("file").required(true).num_args(1)),
         )
+        .subcommand(
+            Command::new("render")
+                .about("Render blaadje code to a WAV file")
+                .arg(Arg::new("file").required(true).num_args(1))
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .short('o')
+                        .long("output")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("seconds")
+                        .short('s')
+                        .long("seconds")
+                        .num_args(1)
+                        .value_parser(value_parser!(f32))
+                        .required_unless_present("beats")
+                        .conflicts_with("beats"),
+                )
+                .arg(
+                    Arg::new("beats")
+                        .short('b')
+                        .long("beats")
+                        .num_args(1)
+                        .value_parser(value_parser!(f32)),
+                )
+                .arg(
+                    Arg::new("bpm")
+                        .long("bpm")
+                        .num_args(1)
+                        .value_parser(value_parser!(f32))
+                        .default_value("120.0"),
+                )
+                .arg(
+                    Arg::new("sample-rate")
+                        .short('r')
+                        .long("sample-rate")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .default_value("44100"),
+                ),
+        )
         .subcommand(
             Command::new("repl")
                 .about("Open interactive REPL environment")
@@ -47,20 +101,18 @@ fn main() -> Result<(), Box<dyn Error>> {
     let env = Arc::new(Mutex::new(env));
     set_prelude(env.clone()).expect("Unable to set prelude");
 
-    thread::spawn(|| {
-        let sys = Box::new(Sys::new());
-        let channels = vec![channel];
-        let mut engine = Engine::<44_100, 128, 128>::new(sys, channels);
-        engine.process();
-    });
-
     match matches.subcommand() {
         Some(("run", matches)) => {
             let file = matches.get_one::<String>("file").unwrap();
+            start_engine(channel);
             run_file(env.clone(), file)
         }
 
+        Some(("render", matches)) => render(env, channel, matches),
+
         Some(("repl", matches)) => {
+            start_engine(channel);
+
             if let Some(file) = matches.get_one::<String>("file") {
                 run_file(env.clone(), file)?;
             }
@@ -70,6 +122,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         Some(("live", matches)) => {
             let file = matches.get_one::<String>("file").unwrap();
+            start_engine(channel);
 
             {
                 env.lock().unwrap().live_mode();
@@ -87,7 +140,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                     Ok(event) => match event.kind {
                         EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
                             // Cleanup
-                            run("(output_disconnect_all)", env.clone());
+                            let _ = run("(output_disconnect_all)", env.clone());
 
                             // Rerun file
                             run_file(env.clone(), file)?;
@@ -104,12 +157,86 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
+fn start_engine(channel: ChannelReceiver) {
+    thread::spawn(|| {
+        let sys = Box::new(Sys::new());
+        let channels = vec![channel];
+        let mut engine = Engine::<SAMPLE_RATE, 128, 128>::new(sys, channels);
+        engine.process();
+    });
+}
+
+fn render(
+    env: Arc<Mutex<Environment>>,
+    channel: ChannelReceiver,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let file = matches.get_one::<String>("file").unwrap();
+    let output = matches.get_one::<String>("output").unwrap();
+    let sample_rate = *matches.get_one::<usize>("sample-rate").unwrap();
+
+    let seconds = match matches.get_one::<f32>("seconds") {
+        Some(seconds) => *seconds,
+        None => {
+            let beats = matches.get_one::<f32>("beats").unwrap();
+            let bpm = matches.get_one::<f32>("bpm").unwrap();
+            beats * 60.0 / bpm
+        }
+    };
+
+    let mut code = String::new();
+    File::open(file)?.read_to_string(&mut code)?;
+
+    // Starting the audio from the program doesn't change the rendered rate
+    let sys = Box::new(NullSystem::new().with_sample_rate(sample_rate));
+    let mut engine = Engine::<SAMPLE_RATE, 128, 128>::new(sys, vec![channel]);
+    engine.set_sample_rate(sample_rate);
+
+    // The program blocks on `call`, so evaluate it next to the engine
+    // answering its messages until it is done.
+    let evaluation = thread::spawn(move || run(&code, env).is_ok());
+
+    while !evaluation.is_finished() {
+        engine.wait_for_messages(RENDER_POLL);
+    }
+
+    let evaluated = evaluation.join().expect("Evaluation thread panicked");
+    engine.process_channels();
+
+    // The error is already printed, don't write a silent file in its place
+    if !evaluated {
+        return Err(format!("Unable to render {}", file).into());
+    }
+
+    let sample_rate = engine.sample_rate();
+    let length = (seconds * sample_rate as f32) as usize * CHANNELS;
+    let mut samples = Vec::with_capacity(length + BLOCK_SIZE * CHANNELS);
+    let mut block = [0.0; BLOCK_SIZE * CHANNELS];
+
+    while samples.len() < length {
+        engine.next_block(&mut block);
+        samples.extend_from_slice(&block);
+    }
+
+    samples.truncate(length);
+
+    write_wav(
+        Path::new(output),
+        &samples,
+        CHANNELS as u16,
+        sample_rate as u32,
+    )?;
+
+    Ok(())
+}
+
 fn run_file(env: Arc<Mutex<Environment>>, path: &str) -> Result<(), Box<dyn Error>> {
     let mut file = File::open(path)?;
     let mut code = String::new();
     file.read_to_string(&mut code)?;
 
-    run(&code, env.clone());
+    // Errors are printed, a broken file shouldn't end the session
+    let _ = run(&code, env.clone());
 
     Ok(())
 }
@@ -124,17 +251,19 @@ fn repl(env: Arc<Mutex<Environment>>) -> Result<(), Box<dyn Error>> {
         let mut input = String::new();
         stdin().read_line(&mut input)?;
 
-        run(&input, env.clone());
+        let _ = run(&input, env.clone());
     }
 }
 
-fn run(code: &str, env: Arc<Mutex<Environment>>) {
+fn run(code: &str, env: Arc<Mutex<Environment>>) -> Result<Blad, blaadje::Error> {
     // Evaluate
     let output = run_with_env(&code, env);
 
     // Print
-    match output {
+    match &output {
         Ok(v) => println!("\x1b[96m{}\x1b[0m", v),
-        Err(v) => println!("\x1b[91mError: {:?}\x1b[0m", v),
+        Err(v) => println!("\x1b[91mError: {}\x1b[0m", v),
     }
+
+    output
 }
@@ -1,8 +1,8 @@
-use blaadje::{Blad, Channel, Literal, System};
+use blaadje::{Channel, ChannelReceiver, System, BLOCK_SIZE, CHANNELS};
 use cpal::{
     available_hosts, default_host, host_from_id,
     traits::{DeviceTrait, HostTrait},
-    Stream,
+    Device, FromSample, Sample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig,
 };
 use midir::{Ignore, MidiInput, MidiInputPort, MidiOutput};
 use ringbuf::{
@@ -12,24 +12,28 @@ use ringbuf::{
     HeapRb, SharedRb,
 };
 use std::error::Error;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+
+type RingConsumer = Caching<Arc<SharedRb<Heap<f32>>>, false, true>;
 
 pub struct Sys {
     stream: Option<Stream>,
     buffer: Option<Caching<Arc<SharedRb<Heap<f32>>>, true, false>>,
     midi_in_port: Option<MidiInputPort>,
-    midi_channel: Arc<Mutex<Channel>>,
+    midi_channel: Channel,
+    midi_receiver: Option<ChannelReceiver>,
 }
 
 impl Sys {
     pub fn new() -> Self {
-        // let midi_in = midi(midi_channel.clone())?;
+        let (midi_channel, midi_receiver) = Channel::new();
 
         Self {
             stream: None,
             buffer: None,
             midi_in_port: None,
-            midi_channel: Arc::new(Mutex::new(Channel::new())),
+            midi_channel,
+            midi_receiver: Some(midi_receiver),
         }
     }
 }
@@ -80,9 +84,9 @@ impl System for Sys {
         host_id: &str,
         device_id: &str,
         buffer_size: usize,
-        _sample_rate: usize,
-        _bit_depth: usize,
-    ) -> Result<(), Box<dyn Error>> {
+        sample_rate: usize,
+        bit_depth: usize,
+    ) -> Result<usize, Box<dyn Error>> {
         let host_id = available_hosts()
             .into_iter()
             .find(|h| h.name() == host_id)
@@ -95,28 +99,54 @@ impl System for Sys {
             .find(|d| d.name().unwrap() == device_id)
             .unwrap();
 
-        let config = device.default_output_config().unwrap();
-        let mut config: cpal::StreamConfig = config.into();
+        // Refuse to run rather than silently detuning everything. Samples are
+        // converted to any format, so the bit depth is only a preference.
+        let config = device
+            .supported_output_configs()?
+            .filter(|c| {
+                c.min_sample_rate().0 as usize <= sample_rate
+                    && c.max_sample_rate().0 as usize >= sample_rate
+            })
+            .max_by_key(|c| {
+                let format = c.sample_format();
+                (format.sample_size() * 8 == bit_depth, format.is_float())
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Device \"{}\" does not support {} Hz",
+                    device_id, sample_rate
+                )
+            })?
+            .with_sample_rate(SampleRate(sample_rate as u32));
+
+        let sample_format = config.sample_format();
+
+        let mut config: StreamConfig = config.into();
         config.buffer_size = cpal::BufferSize::Fixed(buffer_size as u32);
 
-        // The buffer to share samples
-        let ring = HeapRb::<f32>::new(buffer_size * 2);
-        let (producer, mut consumer) = ring.split();
+        // The buffer to share interleaved stereo frames, it has to fit at
+        // least a couple of the engine's blocks
+        let frames = (buffer_size * 2).max(BLOCK_SIZE * 2);
+        let ring = HeapRb::<f32>::new(frames * CHANNELS);
+        let (producer, consumer) = ring.split();
 
-        let output_data_fn = move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-            for sample in data {
-                *sample = match consumer.try_pop() {
-                    Some(s) => s,
-                    None => 0.0,
-                };
-            }
+        let stream = match sample_format {
+            SampleFormat::I8 => build_stream::<i8>(&device, &config, consumer)?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, consumer)?,
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, consumer)?,
+            SampleFormat::I64 => build_stream::<i64>(&device, &config, consumer)?,
+            SampleFormat::U8 => build_stream::<u8>(&device, &config, consumer)?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, consumer)?,
+            SampleFormat::U32 => build_stream::<u32>(&device, &config, consumer)?,
+            SampleFormat::U64 => build_stream::<u64>(&device, &config, consumer)?,
+            SampleFormat::F64 => build_stream::<f64>(&device, &config, consumer)?,
+            _ => build_stream::<f32>(&device, &config, consumer)?,
         };
 
-        let stream = device.build_output_stream(&config, output_data_fn, err_fn, None)?;
         self.stream = Some(stream);
         self.buffer = Some(producer);
 
-        Ok(())
+        Ok(config.sample_rate.0 as usize)
     }
 
     fn stop_audio(&mut self) {
@@ -124,17 +154,22 @@ impl System for Sys {
         self.buffer = None;
     }
 
-    fn buffer_full(&self) -> bool {
+    fn is_running(&self) -> bool {
+        self.buffer.is_some()
+    }
+
+    fn vacant_frames(&self) -> usize {
         if let Some(buffer) = &self.buffer {
-            buffer.is_full()
+            buffer.vacant_len() / CHANNELS
         } else {
-            false
+            // Without a stream nothing takes frames
+            0
         }
     }
 
-    fn push_sample(&mut self, sample: f32) {
+    fn push_frames(&mut self, frames: &[f32]) {
         if let Some(buffer) = &mut self.buffer {
-            buffer.try_push(sample).unwrap();
+            buffer.push_slice(frames);
         }
     }
 
@@ -170,18 +205,15 @@ impl System for Sys {
                 &port,
                 "midir_read_input",
                 move |_, message, _| {
-                    let mut midi_message = 0_usize;
+                    let mut midi_message = 0_u32;
 
-                    for (i, byte) in message.iter().enumerate() {
-                        midi_message += (*byte as usize) << i * 8;
+                    // Channel messages are at most three bytes, longer ones
+                    // like sysex are cut off
+                    for (i, byte) in message.iter().take(4).enumerate() {
+                        midi_message += (*byte as u32) << (i * 8);
                     }
 
-                    let mut channel = channel.lock().unwrap();
-
-                    channel.send(Blad::List(vec![
-                        Blad::Atom(":midi".to_string()),
-                        Blad::Literal(Literal::Usize(midi_message)),
-                    ]));
+                    channel.send_midi(midi_message);
                 },
                 (),
             )
@@ -194,8 +226,53 @@ impl System for Sys {
         self.midi_in_port = None;
     }
 
-    fn get_midi_channel(&self) -> Arc<Mutex<Channel>> {
-        self.midi_channel.clone()
+    fn take_midi_channel(&mut self) -> Option<ChannelReceiver> {
+        self.midi_receiver.take()
+    }
+}
+
+fn build_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    mut consumer: RingConsumer,
+) -> Result<Stream, Box<dyn Error>>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let device_channels = config.channels as usize;
+
+    let output_data_fn = move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+        for frame in data.chunks_mut(device_channels) {
+            // Only take complete frames so left and right never swap
+            let (left, right) = if consumer.occupied_len() >= CHANNELS {
+                (
+                    consumer.try_pop().unwrap_or(0.0),
+                    consumer.try_pop().unwrap_or(0.0),
+                )
+            } else {
+                (0.0, 0.0)
+            };
+
+            write_frame(frame, left, right);
+        }
+    };
+
+    Ok(device.build_output_stream(config, output_data_fn, err_fn, None)?)
+}
+
+/// Write a stereo frame to a device frame with any number of channels
+fn write_frame<T: Sample + FromSample<f32>>(frame: &mut [T], left: f32, right: f32) {
+    match frame {
+        [mono] => *mono = T::from_sample((left + right) * 0.5),
+        [l, r, rest @ ..] => {
+            *l = T::from_sample(left);
+            *r = T::from_sample(right);
+
+            for sample in rest {
+                *sample = T::EQUILIBRIUM;
+            }
+        }
+        [] => (),
     }
 }
 
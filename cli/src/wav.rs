@@ -0,0 +1,40 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const BIT_DEPTH: u16 = 16;
+
+/// Write interleaved samples to a 16 bit PCM WAV file
+pub fn write_wav(path: &Path, samples: &[f32], channels: u16, sample_rate: u32) -> io::Result<()> {
+    let block_align = channels * BIT_DEPTH / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_size = (samples.len() * (BIT_DEPTH / 8) as usize) as u32;
+
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+
+    // RIFF header
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    // Format chunk
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16_u32.to_le_bytes());
+    bytes.extend_from_slice(&1_u16.to_le_bytes());
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&byte_rate.to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&BIT_DEPTH.to_le_bytes());
+
+    // Data chunk
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+
+    for sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    fs::write(path, bytes)
+}